@@ -129,7 +129,7 @@ pub fn part_1(input: &str) -> usize {
 
                 let item_ = new / 3;
 
-                let dst = if item_ % monkeys[idx].test.test_divisor == 0 {
+                let dst = if item_.is_multiple_of(monkeys[idx].test.test_divisor) {
                     monkeys[idx].test.true_dest
                 } else {
                     monkeys[idx].test.false_dest
@@ -165,7 +165,7 @@ pub fn part_2(input: &str) -> usize {
 
                 let item_ = new % modulus;
 
-                let dst = if item_ % monkeys[idx].test.test_divisor == 0 {
+                let dst = if item_.is_multiple_of(monkeys[idx].test.test_divisor) {
                     monkeys[idx].test.true_dest
                 } else {
                     monkeys[idx].test.false_dest
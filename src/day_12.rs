@@ -101,7 +101,7 @@ fn find_ch(grid: &[Vec<char>], ch: char) -> (usize, usize) {
 }
 
 fn raw_neighbors(
-    grid: &Vec<Vec<char>>,
+    grid: &[Vec<char>],
     pos: (usize, usize),
 ) -> impl Iterator<Item = (usize, usize)> + '_ {
     [(-1, 0), (1, 0), (0, -1), (0, 1)]
@@ -116,16 +116,13 @@ fn raw_neighbors(
         .map(|p| (p.0 as usize, p.1 as usize))
 }
 
-fn neighbors(
-    grid: &Vec<Vec<char>>,
-    pos: (usize, usize),
-) -> impl Iterator<Item = (usize, usize)> + '_ {
+fn neighbors(grid: &[Vec<char>], pos: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
     raw_neighbors(grid, pos)
         .filter(move |p| elevation(grid[p.0][p.1]) <= elevation(grid[pos.0][pos.1]) + 1)
 }
 
 fn rev_neighbors(
-    grid: &Vec<Vec<char>>,
+    grid: &[Vec<char>],
     pos: (usize, usize),
 ) -> impl Iterator<Item = (usize, usize)> + '_ {
     raw_neighbors(grid, pos).filter(move |p| neighbors(grid, *p).any(|pp| pp == pos))
@@ -143,12 +140,8 @@ fn elevation(c: char) -> usize {
     c as usize - 'a' as usize
 }
 
-fn dijkstra<
-    'a,
-    N: Fn(&'a Vec<Vec<char>>, (usize, usize)) -> I,
-    I: Iterator<Item = (usize, usize)>,
->(
-    grid: &'a Vec<Vec<char>>,
+fn dijkstra<'a, N: Fn(&'a [Vec<char>], (usize, usize)) -> I, I: Iterator<Item = (usize, usize)>>(
+    grid: &'a [Vec<char>],
     start: char,
     neighbor_func: N,
 ) -> HashMap<(usize, usize), usize> {
@@ -189,7 +182,7 @@ pub fn part_1(input: &str) -> usize {
     dist[&find_ch(&grid, 'E')]
 }
 
-fn positions(grid: &Vec<Vec<char>>) -> impl Iterator<Item = (usize, usize)> + '_ {
+fn positions(grid: &[Vec<char>]) -> impl Iterator<Item = (usize, usize)> + '_ {
     (0..grid.len()).flat_map(move |i| (0..grid[i].len()).map(move |j| (i, j)))
 }
 
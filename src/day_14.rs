@@ -202,7 +202,7 @@
 // Using your scan, simulate the falling sand until the source of the sand
 // becomes blocked. How many units of sand come to rest?
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap};
 
 #[derive(Copy, Clone, Debug)]
 enum Segment {
@@ -289,10 +289,10 @@ pub fn part_1(input: &str) -> usize {
                 if pos.1 > lowest_rock {
                     return i - 1;
                 }
-                if grid.get(&pos).is_none() {
+                if let Entry::Vacant(e) = grid.entry(pos) {
                     let at_rest = sand_pos == pos;
                     if at_rest {
-                        grid.insert(pos, V::S);
+                        e.insert(V::S);
                         break 'inner;
                     }
                     sand_pos = pos;
@@ -312,7 +312,7 @@ pub fn part_2(input: &str) -> usize {
     for i in 1.. {
         let mut sand_pos = sand_source;
 
-        if grid.get(&sand_pos).is_some() {
+        if grid.contains_key(&sand_pos) {
             return i - 1;
         }
 
@@ -324,7 +324,7 @@ pub fn part_2(input: &str) -> usize {
                 sand_pos,
             ];
             'search: for pos in seq {
-                let filled = grid.get(&pos).is_some() || pos.1 >= lowest_rock + 2;
+                let filled = grid.contains_key(&pos) || pos.1 >= lowest_rock + 2;
                 if !filled {
                     let at_rest = sand_pos == pos;
                     if at_rest {
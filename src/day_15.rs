@@ -148,6 +148,8 @@
 
 use std::collections::{HashMap, HashSet};
 
+use crate::parallel::Parallelism;
+
 fn parse_coord(coord: &str) -> (i64, i64) {
     let (x, y) = coord.trim().split_once(", ").unwrap();
     (
@@ -322,20 +324,14 @@ impl Rectangle {
     }
 }
 
-/// Solve using a quad-tree recursive search. A rectangle *may* contain the beacon if it has a
-/// corner which is out of the range of each sensor (not necessarily the same corner).
-pub fn part_2(input: &str, max_range: i64) -> i64 {
-    let (sensor_locs, _) = parse(input);
-
-    let mut stk = vec![Rectangle {
-        x_range: (0, max_range),
-        y_range: (0, max_range),
-    }];
+/// Searches `rect` for a point out of range of every sensor.
+fn search(rect: Rectangle, sensor_locs: &SensorWithDist) -> Option<i64> {
+    let mut stk = vec![rect];
 
     while let Some(rect) = stk.pop() {
         if rect.c1() == rect.c2() {
             if sensor_locs.iter().all(|(s, d)| dist(*s, rect.c1()) > *d) {
-                return tuning_frequency(rect.c1());
+                return Some(tuning_frequency(rect.c1()));
             }
         } else {
             let rects = rect.split();
@@ -351,7 +347,35 @@ pub fn part_2(input: &str, max_range: i64) -> i64 {
         }
     }
 
-    unreachable!()
+    None
+}
+
+/// Solve using a quad-tree recursive search. A rectangle *may* contain the beacon if it has a
+/// corner which is out of the range of each sensor (not necessarily the same corner).
+pub fn part_2(input: &str, max_range: i64) -> i64 {
+    part_2_with(input, max_range, Parallelism::from_env())
+}
+
+/// Like [`part_2`], searching each quarter of the area on its own thread.
+pub fn part_2_with(input: &str, max_range: i64, parallelism: Parallelism) -> i64 {
+    let (sensor_locs, _) = parse(input);
+
+    let area = Rectangle {
+        x_range: (0, max_range),
+        y_range: (0, max_range),
+    };
+    let quarters = if area.c1() == area.c2() {
+        vec![area]
+    } else {
+        area.split().into_iter().filter(|r| *r != area).collect()
+    };
+
+    parallelism
+        .map(&quarters, |q| search(*q, &sensor_locs))
+        .into_iter()
+        .flatten()
+        .next()
+        .unwrap()
 }
 
 fn tf(p: (i64, i64)) -> (i64, i64) {
@@ -432,7 +456,8 @@ pub fn part_2_sensors_squared(input: &str, max_range: i64) -> i64 {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_15::{part_1, part_2, part_2_sensors_squared, part_2_slow};
+    use crate::day_15::{part_1, part_2, part_2_sensors_squared, part_2_slow, part_2_with};
+    use crate::parallel::Parallelism;
 
     const INPUTS: &str = r#"Sensor at x=2, y=18: closest beacon is at x=-2, y=15
 Sensor at x=9, y=16: closest beacon is at x=10, y=16
@@ -477,4 +502,13 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3"#;
             11914583249288
         );
     }
+
+    #[test]
+    pub fn test_day_15_sequential_matches_parallel() {
+        let input = include_str!("input/day_15.txt");
+        for p in [Parallelism::Sequential, Parallelism::Threads(4)] {
+            assert_eq!(part_2_with(INPUTS, 20, p), 56000011);
+            assert_eq!(part_2_with(input, 4000000, p), 11914583249288);
+        }
+    }
 }
@@ -2,6 +2,8 @@
 
 use std::collections::{HashMap, VecDeque};
 
+use crate::parallel::Parallelism;
+
 #[derive(Clone, Debug)]
 pub struct Valve {
     flow_rate: i64,
@@ -171,6 +173,11 @@ pub fn part_1(input: &str) -> i64 {
 }
 
 pub fn part_2(input: &str) -> i64 {
+    part_2_with(input, Parallelism::from_env())
+}
+
+/// Like [`part_2`], pairing each of my routes with the elephant's on worker threads.
+pub fn part_2_with(input: &str, parallelism: Parallelism) -> i64 {
     let valves = parse(input);
     let mut valve_list = valves.keys().cloned().collect::<Vec<_>>();
     valve_list.sort();
@@ -200,22 +207,26 @@ pub fn part_2(input: &str) -> i64 {
         26,
     );
 
-    let mut max = 0;
-
-    for (me, f_me) in &ending_pressures {
-        for (elephant, f_elephant) in &ending_pressures {
-            if me & elephant == 0 {
-                max = max.max(*f_me + *f_elephant);
-            }
-        }
-    }
-
-    max
+    let ending_pressures = ending_pressures.into_iter().collect::<Vec<_>>();
+
+    parallelism
+        .map(&ending_pressures, |(me, f_me)| {
+            ending_pressures
+                .iter()
+                .filter(|(elephant, _)| me & elephant == 0)
+                .map(|(_, f_elephant)| f_me + f_elephant)
+                .max()
+                .unwrap_or(0)
+        })
+        .into_iter()
+        .max()
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_16::{part_1, part_2};
+    use crate::day_16::{part_1, part_2, part_2_with};
+    use crate::parallel::Parallelism;
 
     const INPUTS: &str = r#"Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
 Valve BB has flow rate=13; tunnels lead to valves CC, AA
@@ -247,4 +258,13 @@ Valve JJ has flow rate=21; tunnel leads to valve II"#;
     pub fn test_day_16_part2() {
         assert_eq!(part_2(include_str!("input/day_16.txt")), 2752);
     }
+
+    #[test]
+    pub fn test_day_16_sequential_matches_parallel() {
+        let input = include_str!("input/day_16.txt");
+        for p in [Parallelism::Sequential, Parallelism::Threads(4)] {
+            assert_eq!(part_2_with(INPUTS, p), 1707);
+            assert_eq!(part_2_with(input, p), 2752);
+        }
+    }
 }
@@ -1,7 +1,4 @@
-use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    hash::{Hash, Hasher},
-};
+use std::collections::HashMap;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum Rock {
@@ -91,15 +88,15 @@ impl Room {
         }
     }
 
-    fn top_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.arr
-            .iter()
-            .rev()
-            .take(20)
-            .collect::<Vec<_>>()
-            .hash(&mut hasher);
-        hasher.finish()
+    /// The top `depth` rows of the tower, highest first, with the floor drawn
+    /// as solid rows if the tower isn't that tall yet.
+    fn top_rows(&self, depth: usize) -> Vec<u8> {
+        (1..=depth)
+            .map(|d| match self.arr.len().checked_sub(d) {
+                Some(y) => self.arr[y],
+                None => 0b111_1111,
+            })
+            .collect()
     }
 }
 
@@ -127,14 +124,13 @@ fn go_down(room: &Room, pos: (usize, usize), rock: Rock) -> Option<(usize, usize
     }
 }
 
-fn place_rock(
-    room: &mut Room,
-    gas_iter: &mut impl Iterator<Item = (usize, bool)>,
-    rock: Rock,
-) -> usize {
+/// Drops a rock and returns the lowest row it looked at, which is the one
+/// just below where it came to rest (`-1` for the floor).
+fn place_rock(room: &mut Room, gas: &[bool], gas_idx: &mut usize, rock: Rock) -> isize {
     let mut pos = (2, room.highest_occupied_row() + 3);
     loop {
-        let (d_idx, r) = gas_iter.next().unwrap();
+        let r = gas[*gas_idx];
+        *gas_idx = (*gas_idx + 1) % gas.len();
         if r {
             if let Some(new_pos) = go_right(room, pos, rock) {
                 pos = new_pos;
@@ -147,69 +143,127 @@ fn place_rock(
             pos = new_pos;
         } else {
             room.emplace(pos, rock);
-            break d_idx;
+            return pos.1 as isize - 1;
         }
     }
 }
 
-fn solve(input: &str, num_iter: usize) -> usize {
-    let mut room = Room::new();
-    let mut gas_iter = input
-        .chars()
-        .filter(|c| *c == '>' || *c == '<')
-        .map(|c| c == '>')
-        .enumerate()
-        .cycle();
-
-    let mut cache = HashMap::new();
-
-    for i in 0..num_iter {
-        let rock = Rock::nth_rock(i);
-        let d_idx = place_rock(&mut room, &mut gas_iter, rock);
-
-        // We notice that there's a repeating cycle in the output, so let's try
-        // to find it via the janky method. This makes sense, because the two
-        // inputs are themselves cyclic -- a cycle of gas and a cycle of rocks.
-        if let Some((first_observed_at, highest_row_when_observed)) =
-            cache.get(&(rock, d_idx, room.top_hash()))
-        {
-            // p is the observed period of the cycle, since we should hit the
-            // cache at the second cycle.
-            let p = i - first_observed_at;
-
-            // Do some modular arithmetic to find the period we are in at the
-            // end point.
-            if (num_iter - 1) % p == i % p {
-                let rows_gained_per_cycle = room.highest_occupied_row() - highest_row_when_observed;
-                let remaining_cycles = (num_iter - i - 1) / p;
-                return room.highest_occupied_row() + remaining_cycles * rows_gained_per_cycle;
+/// The repetition found in the tower: after `prefix` rocks, every further
+/// `period` rocks add exactly `height_per_period` rows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub period: usize,
+    pub height_per_period: usize,
+}
+
+/// A tower simulated just far enough to find its cycle, which can then answer
+/// height queries for any number of rocks.
+#[derive(Debug, Clone)]
+pub struct Tower {
+    /// `heights[n]` is the height of the tower after `n` rocks have landed.
+    heights: Vec<usize>,
+    cycle: Cycle,
+}
+
+impl Tower {
+    pub fn simulate(input: &str) -> Tower {
+        let gas = input
+            .chars()
+            .filter(|c| *c == '>' || *c == '<')
+            .map(|c| c == '>')
+            .collect::<Vec<_>>();
+        assert!(!gas.is_empty(), "no gas jets in input");
+
+        let mut depth = 32;
+        loop {
+            if let Some(tower) = Tower::find_cycle(&gas, depth) {
+                return tower;
             }
-        } else {
-            // The two cycle indexes are the rock and the gas index. We also
-            // hash the top of the room so far.
-            cache.insert(
-                (rock, d_idx, room.top_hash()),
-                // Store the current iterator and the highest row at the time in
-                // the cache.
-                (i, room.highest_occupied_row()),
-            );
+            depth *= 2;
+        }
+    }
+
+    /// Looks for a cycle keyed on the next rock, the next gas jet and the top
+    /// `depth` rows of the tower.
+    ///
+    /// A rock only depends on the cells it looks at. If the state after rock
+    /// `m` matches the state after rock `p`, and none of the rocks in between
+    /// looked below the top `depth` rows as they were after rock `p`, then
+    /// rocks `m..` replay rocks `p..` exactly, just higher up, and so on
+    /// forever. If some rock did look deeper, the window was too shallow to
+    /// prove anything and we give up, so the caller can retry with a deeper
+    /// one.
+    fn find_cycle(gas: &[bool], depth: usize) -> Option<Tower> {
+        let mut room = Room::new();
+        let mut gas_idx = 0;
+        let mut heights = vec![0];
+        // `lowest[n]` is the lowest row rock `n` looked at.
+        let mut lowest = vec![];
+        let mut seen = HashMap::new();
+        seen.insert((0, 0, room.top_rows(depth)), 0);
+
+        for n in 1.. {
+            lowest.push(place_rock(
+                &mut room,
+                gas,
+                &mut gas_idx,
+                Rock::nth_rock(n - 1),
+            ));
+            heights.push(room.highest_occupied_row());
+
+            let state = (n % 5, gas_idx, room.top_rows(depth));
+            if let Some(&prefix) = seen.get(&state) {
+                let bottom = heights[prefix] as isize - depth as isize;
+                if lowest[prefix..n].iter().any(|y| *y < bottom) {
+                    return None;
+                }
+                let cycle = Cycle {
+                    prefix,
+                    period: n - prefix,
+                    height_per_period: heights[n] - heights[prefix],
+                };
+                return Some(Tower { heights, cycle });
+            }
+            seen.insert(state, n);
         }
+
+        unreachable!()
+    }
+
+    pub fn cycle(&self) -> Cycle {
+        self.cycle
     }
 
-    room.highest_occupied_row()
+    /// Height of the tower after `num_rocks` rocks have come to rest.
+    pub fn height_after(&self, num_rocks: usize) -> usize {
+        let Cycle {
+            prefix,
+            period,
+            height_per_period,
+        } = self.cycle;
+
+        if num_rocks < self.heights.len() {
+            return self.heights[num_rocks];
+        }
+
+        let periods = (num_rocks - prefix) / period;
+        let remainder = (num_rocks - prefix) % period;
+        self.heights[prefix + remainder] + periods * height_per_period
+    }
 }
 
 pub fn part_1(input: &str) -> usize {
-    solve(input, 2022)
+    Tower::simulate(input).height_after(2022)
 }
 
 pub fn part_2(input: &str) -> usize {
-    solve(input, 1000000000000)
+    Tower::simulate(input).height_after(1000000000000)
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_17::{part_1, part_2};
+    use crate::day_17::{part_1, part_2, place_rock, Rock, Room, Tower};
 
     const INPUTS: &str = r#">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"#;
 
@@ -232,4 +286,42 @@ pub mod tests {
     pub fn test_day_17_part2() {
         assert_eq!(part_2(include_str!("input/day_17.txt")), 1537175792495);
     }
+
+    #[test]
+    pub fn test_day_17_cycle_matches_simulation() {
+        for input in [
+            INPUTS,
+            include_str!("input/day_17.txt"),
+            "<",
+            ">",
+            "<<<<<<<<>",
+            "><",
+        ] {
+            let tower = Tower::simulate(input);
+            let cycle = tower.cycle();
+            assert!(cycle.period > 0);
+
+            let gas = input
+                .chars()
+                .filter(|c| *c == '>' || *c == '<')
+                .map(|c| c == '>')
+                .collect::<Vec<_>>();
+            let mut room = Room::new();
+            let mut gas_idx = 0;
+            for n in 0..cycle.prefix + 3 * cycle.period {
+                assert_eq!(tower.height_after(n), room.highest_occupied_row());
+                place_rock(&mut room, &gas, &mut gas_idx, Rock::nth_rock(n));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_17_columns_open_to_the_floor() {
+        // Every rock is pushed against the left wall, leaving the right-hand
+        // columns open all the way down, which no rock ever enters.
+        let tower = Tower::simulate("<");
+        assert_eq!(tower.cycle().period, 5);
+        assert_eq!(tower.height_after(5), 11);
+        assert_eq!(tower.height_after(1000000000000), 2200000000000);
+    }
 }
@@ -62,7 +62,7 @@
 
 use std::collections::HashSet;
 
-fn parse(input: &str) -> HashSet<(i64, i64, i64)> {
+pub(crate) fn parse(input: &str) -> HashSet<(i64, i64, i64)> {
     let mut pts: HashSet<(i64, i64, i64)> = HashSet::new();
     for line in input.lines() {
         if line.is_empty() {
@@ -86,7 +86,7 @@ pub fn part_1(input: &str) -> usize {
         .count()
 }
 
-fn adjacents((x, y, z): (i64, i64, i64)) -> [(i64, i64, i64); 6] {
+pub(crate) fn adjacents((x, y, z): (i64, i64, i64)) -> [(i64, i64, i64); 6] {
     [
         (x - 1, y, z),
         (x + 1, y, z),
@@ -0,0 +1,404 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::day_18::{adjacents, parse};
+
+pub type Voxel = (i64, i64, i64);
+
+/// Axis-aligned box containing every voxel, with inclusive bounds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Voxel,
+    pub max: Voxel,
+}
+
+impl BoundingBox {
+    fn of(voxels: impl IntoIterator<Item = Voxel>) -> Option<BoundingBox> {
+        let mut iter = voxels.into_iter();
+        let first = iter.next()?;
+        Some(iter.fold(
+            BoundingBox {
+                min: first,
+                max: first,
+            },
+            |b, v| BoundingBox {
+                min: (b.min.0.min(v.0), b.min.1.min(v.1), b.min.2.min(v.2)),
+                max: (b.max.0.max(v.0), b.max.1.max(v.1), b.max.2.max(v.2)),
+            },
+        ))
+    }
+
+    pub fn contains(&self, v: Voxel) -> bool {
+        (self.min.0..=self.max.0).contains(&v.0)
+            && (self.min.1..=self.max.1).contains(&v.1)
+            && (self.min.2..=self.max.2).contains(&v.2)
+    }
+
+    pub fn dimensions(&self) -> (i64, i64, i64) {
+        (
+            self.max.0 - self.min.0 + 1,
+            self.max.1 - self.min.1 + 1,
+            self.max.2 - self.min.2 + 1,
+        )
+    }
+
+    pub fn volume(&self) -> i64 {
+        let (x, y, z) = self.dimensions();
+        x * y * z
+    }
+
+    fn grow(&self, n: i64) -> BoundingBox {
+        BoundingBox {
+            min: (self.min.0 - n, self.min.1 - n, self.min.2 - n),
+            max: (self.max.0 + n, self.max.1 + n, self.max.2 + n),
+        }
+    }
+}
+
+/// An air pocket fully enclosed by lava.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cavity {
+    pub voxels: BTreeSet<Voxel>,
+    /// Number of lava faces bordering the pocket.
+    pub surface_area: usize,
+}
+
+impl Cavity {
+    pub fn volume(&self) -> usize {
+        self.voxels.len()
+    }
+}
+
+/// One unit square on the boundary of a voxel, identified by the voxel it
+/// belongs to and the axis (0, 1 or 2 for x, y or z) and sign of its outward
+/// normal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Face {
+    pub voxel: Voxel,
+    pub axis: usize,
+    pub positive: bool,
+}
+
+impl Face {
+    pub fn normal(&self) -> Voxel {
+        let s = if self.positive { 1 } else { -1 };
+        match self.axis {
+            0 => (s, 0, 0),
+            1 => (0, s, 0),
+            2 => (0, 0, s),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The four corners of the face, counter-clockwise when viewed from
+    /// outside so that the winding agrees with the normal.
+    pub fn corners(&self) -> [Voxel; 4] {
+        let unit = |axis: usize| match axis {
+            0 => [1, 0, 0],
+            1 => [0, 1, 0],
+            _ => [0, 0, 1],
+        };
+        let mut base = [self.voxel.0, self.voxel.1, self.voxel.2];
+        if self.positive {
+            base[self.axis] += 1;
+        }
+        // u x v points along the axis, since they follow it cyclically.
+        let (u, v) = (unit((self.axis + 1) % 3), unit((self.axis + 2) % 3));
+        let at = |du: i64, dv: i64| {
+            (
+                base[0] + du * u[0] + dv * v[0],
+                base[1] + du * u[1] + dv * v[1],
+                base[2] + du * u[2] + dv * v[2],
+            )
+        };
+
+        let corners = [at(0, 0), at(1, 0), at(1, 1), at(0, 1)];
+        if self.positive {
+            corners
+        } else {
+            [corners[0], corners[3], corners[2], corners[1]]
+        }
+    }
+}
+
+/// Splits a set of voxels into its face-connected components, ordered by
+/// their smallest voxel.
+pub fn components(voxels: &HashSet<Voxel>) -> Vec<BTreeSet<Voxel>> {
+    let mut labels = HashMap::new();
+    let mut components = vec![];
+
+    let mut sorted = voxels.iter().copied().collect::<Vec<_>>();
+    sorted.sort();
+
+    for start in sorted {
+        if labels.contains_key(&start) {
+            continue;
+        }
+        let label = components.len();
+        let mut component = BTreeSet::new();
+        let mut q = vec![start];
+        labels.insert(start, label);
+
+        while let Some(v) = q.pop() {
+            component.insert(v);
+            for vv in adjacents(v) {
+                if voxels.contains(&vv) && !labels.contains_key(&vv) {
+                    labels.insert(vv, label);
+                    q.push(vv);
+                }
+            }
+        }
+        components.push(component);
+    }
+
+    components
+}
+
+#[derive(Debug, Clone)]
+pub struct Droplet {
+    voxels: HashSet<Voxel>,
+}
+
+impl Droplet {
+    pub fn parse(input: &str) -> Droplet {
+        Droplet {
+            voxels: parse(input),
+        }
+    }
+
+    pub fn voxels(&self) -> &HashSet<Voxel> {
+        &self.voxels
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::of(self.voxels.iter().copied())
+    }
+
+    /// The separate pieces of lava making up the droplet.
+    pub fn components(&self) -> Vec<BTreeSet<Voxel>> {
+        components(&self.voxels)
+    }
+
+    /// Every face not touching another lava voxel, including those facing
+    /// into cavities.
+    pub fn surface_area(&self) -> usize {
+        self.voxels
+            .iter()
+            .copied()
+            .flat_map(adjacents)
+            .filter(|p| !self.voxels.contains(p))
+            .count()
+    }
+
+    /// The air connected to the outside of the droplet, limited to a box one
+    /// voxel larger than the droplet on every side.
+    fn exterior(&self) -> HashSet<Voxel> {
+        let bounds = match self.bounding_box() {
+            Some(b) => b.grow(1),
+            None => return HashSet::new(),
+        };
+
+        let mut visited = HashSet::new();
+        let mut q = vec![bounds.min];
+        visited.insert(bounds.min);
+
+        while let Some(v) = q.pop() {
+            for vv in adjacents(v) {
+                if bounds.contains(vv) && !self.voxels.contains(&vv) && visited.insert(vv) {
+                    q.push(vv);
+                }
+            }
+        }
+
+        visited
+    }
+
+    /// The faces which can be seen from outside the droplet, in a stable
+    /// order.
+    pub fn exterior_faces(&self) -> Vec<Face> {
+        let exterior = self.exterior();
+        let mut faces = vec![];
+        for &voxel in &self.voxels {
+            for (idx, neighbor) in adjacents(voxel).into_iter().enumerate() {
+                if exterior.contains(&neighbor) {
+                    faces.push(Face {
+                        voxel,
+                        axis: idx / 2,
+                        positive: idx % 2 == 1,
+                    });
+                }
+            }
+        }
+        faces.sort();
+        faces
+    }
+
+    pub fn exterior_surface_area(&self) -> usize {
+        self.exterior_faces().len()
+    }
+
+    /// The air pockets trapped inside the droplet.
+    pub fn cavities(&self) -> Vec<Cavity> {
+        let bounds = match self.bounding_box() {
+            Some(b) => b,
+            None => return vec![],
+        };
+        let exterior = self.exterior();
+
+        let mut trapped = HashSet::new();
+        for x in bounds.min.0..=bounds.max.0 {
+            for y in bounds.min.1..=bounds.max.1 {
+                for z in bounds.min.2..=bounds.max.2 {
+                    let v = (x, y, z);
+                    if !self.voxels.contains(&v) && !exterior.contains(&v) {
+                        trapped.insert(v);
+                    }
+                }
+            }
+        }
+
+        components(&trapped)
+            .into_iter()
+            .map(|voxels| {
+                let surface_area = voxels
+                    .iter()
+                    .copied()
+                    .flat_map(adjacents)
+                    .filter(|p| self.voxels.contains(p))
+                    .count();
+                Cavity {
+                    voxels,
+                    surface_area,
+                }
+            })
+            .collect()
+    }
+
+    /// Wavefront OBJ mesh of the exterior surface, as one quad per face.
+    pub fn to_obj(&self) -> String {
+        let mut vertices: HashMap<Voxel, usize> = HashMap::new();
+        let mut out = String::new();
+        let mut quads = String::new();
+
+        for face in self.exterior_faces() {
+            let mut indices = [0; 4];
+            for (i, corner) in face.corners().into_iter().enumerate() {
+                let next = vertices.len() + 1;
+                indices[i] = *vertices.entry(corner).or_insert_with(|| {
+                    writeln!(out, "v {} {} {}", corner.0, corner.1, corner.2).unwrap();
+                    next
+                });
+            }
+            writeln!(
+                quads,
+                "f {} {} {} {}",
+                indices[0], indices[1], indices[2], indices[3]
+            )
+            .unwrap();
+        }
+
+        out.push_str(&quads);
+        out
+    }
+
+    /// ASCII STL mesh of the exterior surface, as two triangles per face.
+    pub fn to_stl(&self) -> String {
+        let mut out = String::from("solid droplet\n");
+
+        for face in self.exterior_faces() {
+            let n = face.normal();
+            let c = face.corners();
+            for tri in [[c[0], c[1], c[2]], [c[0], c[2], c[3]]] {
+                writeln!(out, "  facet normal {} {} {}", n.0, n.1, n.2).unwrap();
+                writeln!(out, "    outer loop").unwrap();
+                for v in tri {
+                    writeln!(out, "      vertex {} {} {}", v.0, v.1, v.2).unwrap();
+                }
+                writeln!(out, "    endloop").unwrap();
+                writeln!(out, "  endfacet").unwrap();
+            }
+        }
+
+        out.push_str("endsolid droplet\n");
+        out
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::day_18_voxel::{BoundingBox, Droplet, Face};
+
+    const INPUTS: &str = r#"2,2,2
+1,2,2
+3,2,2
+2,1,2
+2,3,2
+2,2,1
+2,2,3
+2,2,4
+2,2,6
+1,2,5
+3,2,5
+2,1,5
+2,3,5"#;
+
+    #[test]
+    pub fn test_day_18_voxel_surface_areas() {
+        let droplet = Droplet::parse(INPUTS);
+        assert_eq!(droplet.surface_area(), 64);
+        assert_eq!(droplet.exterior_surface_area(), 58);
+
+        let droplet = Droplet::parse(include_str!("input/day_18.txt"));
+        assert_eq!(droplet.surface_area(), 3412);
+        assert_eq!(droplet.exterior_surface_area(), 2018);
+    }
+
+    #[test]
+    pub fn test_day_18_voxel_example_cavities() {
+        let droplet = Droplet::parse(INPUTS);
+        let cavities = droplet.cavities();
+        assert_eq!(cavities.len(), 1);
+        assert_eq!(cavities[0].volume(), 1);
+        assert_eq!(cavities[0].voxels.first(), Some(&(2, 2, 5)));
+        assert_eq!(cavities[0].surface_area, 6);
+
+        assert_eq!(
+            droplet.bounding_box(),
+            Some(BoundingBox {
+                min: (1, 1, 1),
+                max: (3, 3, 6)
+            })
+        );
+        assert_eq!(droplet.components().len(), 6);
+    }
+
+    #[test]
+    pub fn test_day_18_voxel_cavities_account_for_surface() {
+        let droplet = Droplet::parse(include_str!("input/day_18.txt"));
+        let interior: usize = droplet.cavities().iter().map(|c| c.surface_area).sum();
+        assert_eq!(
+            droplet.surface_area(),
+            droplet.exterior_surface_area() + interior
+        );
+    }
+
+    #[test]
+    pub fn test_day_18_voxel_mesh_export() {
+        let droplet = Droplet::parse("0,0,0");
+        let obj = droplet.to_obj();
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 8);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 6);
+
+        let stl = droplet.to_stl();
+        assert_eq!(stl.matches("facet normal").count(), 12);
+        assert!(stl.starts_with("solid droplet\n"));
+        assert!(stl.ends_with("endsolid droplet\n"));
+
+        let face = Face {
+            voxel: (0, 0, 0),
+            axis: 0,
+            positive: true,
+        };
+        assert_eq!(face.corners(), [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)]);
+    }
+}
@@ -104,95 +104,51 @@
 // of geodes you could open using each of the first three blueprints. What do
 // you get if you multiply these numbers together?
 
-use std::collections::{HashSet, VecDeque};
-
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
-struct Resources {
-    ore: i32,
-    clay: i32,
-    obsidian: i32,
-    geodes: i32,
-}
+use std::collections::HashMap;
+use std::fmt::Write;
 
-impl Resources {
-    fn can_build(self, cost: Resources) -> bool {
-        self.ore >= cost.ore
-            && self.clay >= cost.clay
-            && self.obsidian >= cost.obsidian
-            && self.geodes >= cost.geodes
-    }
+use crate::parallel::Parallelism;
 
-    fn ore_unit() -> Self {
-        Resources {
-            ore: 1,
-            ..Default::default()
-        }
-    }
-    fn clay_unit() -> Self {
-        Resources {
-            clay: 1,
-            ..Default::default()
-        }
-    }
-    fn obsidian_unit() -> Self {
-        Resources {
-            obsidian: 1,
-            ..Default::default()
-        }
-    }
-    fn geodes_unit() -> Self {
-        Resources {
-            geodes: 1,
-            ..Default::default()
-        }
-    }
+/// A robot which collects one unit of `produces` per minute, once built from
+/// `cost` (indexed by resource).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    pub produces: usize,
+    pub cost: Vec<i32>,
 }
 
-impl std::ops::Add for Resources {
-    type Output = Resources;
-    fn add(self, rhs: Self) -> Self::Output {
-        Resources {
-            ore: self.ore + rhs.ore,
-            clay: self.clay + rhs.clay,
-            obsidian: self.obsidian + rhs.obsidian,
-            geodes: self.geodes + rhs.geodes,
-        }
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blueprint {
+    pub id: i32,
+    /// Every resource mentioned by the blueprint, in order of first
+    /// appearance.
+    pub resources: Vec<String>,
+    pub recipes: Vec<Recipe>,
 }
 
-impl std::ops::Sub for Resources {
-    type Output = Resources;
-    fn sub(self, rhs: Self) -> Self::Output {
-        Resources {
-            ore: self.ore - rhs.ore,
-            clay: self.clay - rhs.clay,
-            obsidian: self.obsidian - rhs.obsidian,
-            geodes: self.geodes - rhs.geodes,
-        }
+impl Blueprint {
+    pub fn resource(&self, name: &str) -> Option<usize> {
+        self.resources.iter().position(|r| r == name)
     }
-}
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
-struct Blueprint {
-    id: i32,
-    ore_bot_cost: Resources,
-    clay_bot_cost: Resources,
-    obsidian_bot_cost: Resources,
-    geode_bot_cost: Resources,
-}
+    fn robot_name(&self, resource: usize) -> String {
+        match self.resources[resource].as_str() {
+            "geode" => "geode-cracking robot".to_string(),
+            name => format!("{name}-collecting robot"),
+        }
+    }
 
-impl Blueprint {
-    fn all_costs(self) -> [Resources; 4] {
-        [
-            self.ore_bot_cost,
-            self.clay_bot_cost,
-            self.obsidian_bot_cost,
-            self.geode_bot_cost,
-        ]
+    /// The robots the factory starts with: exactly one ore-collecting robot.
+    pub fn initial_robots(&self) -> Vec<i32> {
+        let mut robots = vec![0; self.resources.len()];
+        if let Some(ore) = self.resource("ore") {
+            robots[ore] = 1;
+        }
+        robots
     }
 }
 
-fn parse(input: &str) -> Vec<Blueprint> {
+pub fn parse(input: &str) -> Vec<Blueprint> {
     let mut blueprints = vec![];
     for line in input.lines() {
         if line.is_empty() {
@@ -201,252 +157,393 @@ fn parse(input: &str) -> Vec<Blueprint> {
 
         let line = line.strip_prefix("Blueprint ").unwrap();
         let (num, line) = line.split_once(':').unwrap();
-        let mut blueprint = Blueprint {
-            id: num.parse().unwrap(),
-            ..Default::default()
+
+        let mut resource_ids = HashMap::new();
+        let mut resources = vec![];
+        let mut id_of = |name: &str| {
+            *resource_ids.entry(name.to_string()).or_insert_with(|| {
+                resources.push(name.to_string());
+                resources.len() - 1
+            })
         };
 
+        let mut recipes = vec![];
         for bot_line in line.split('.').map(|l| l.trim()) {
             if bot_line.is_empty() {
                 continue;
             }
             let bot_line = bot_line.split_once("Each ").unwrap().1;
             let (typ, cost) = bot_line.split_once(" robot costs ").unwrap();
+            let produces = id_of(typ);
 
-            let mut c = Resources::default();
-            let costs = cost.split(" and ");
-
-            for cost_part in costs {
-                let (amt, typ__) = cost_part.split_once(' ').unwrap();
+            let mut costs = vec![];
+            for cost_part in cost.split(" and ") {
+                let (amt, typ) = cost_part.split_once(' ').unwrap();
+                costs.push((id_of(typ), amt.parse::<i32>().unwrap()));
+            }
+            recipes.push((produces, costs));
+        }
 
-                match typ__ {
-                    "ore" => c.ore = amt.parse().unwrap(),
-                    "clay" => c.clay = amt.parse().unwrap(),
-                    "obsidian" => c.obsidian = amt.parse().unwrap(),
-                    _ => unreachable!(),
+        let recipes = recipes
+            .into_iter()
+            .map(|(produces, costs)| {
+                let mut cost = vec![0; resources.len()];
+                for (r, amt) in costs {
+                    cost[r] += amt;
                 }
-            }
+                Recipe { produces, cost }
+            })
+            .collect();
 
-            match typ {
-                "ore" => blueprint.ore_bot_cost = c,
-                "clay" => blueprint.clay_bot_cost = c,
-                "obsidian" => blueprint.obsidian_bot_cost = c,
-                "geode" => blueprint.geode_bot_cost = c,
-                _ => unreachable!(),
-            }
-        }
-        blueprints.push(blueprint);
+        blueprints.push(Blueprint {
+            id: num.trim().parse().unwrap(),
+            resources,
+            recipes,
+        });
     }
 
     blueprints
 }
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
-struct State {
-    active_bots: Resources,
-    resources: Resources,
-    no_build_build_mask: Resources,
-    minute: u8,
+/// Start building the robot from `recipe` at the beginning of `minute`
+/// (counting from 1). It is ready to collect from the following minute.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Build {
+    pub minute: i32,
+    pub recipe: usize,
 }
 
-impl State {
-    fn build_bot(self, new_bot: Resources, cost: Resources, max_costs: Resources) -> Self {
-        let mut new_rsc = self.resources - cost + self.active_bots;
-        let new_bots = self.active_bots + new_bot;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    /// How much of the objective the plan ends up with.
+    pub amount: i32,
+    pub builds: Vec<Build>,
+}
 
-        if new_bots.ore == max_costs.ore {
-            new_rsc.ore = new_rsc.ore.min(max_costs.ore);
-        }
-        if new_bots.clay == max_costs.clay {
-            new_rsc.clay = new_rsc.clay.min(max_costs.clay);
+struct Search<'a> {
+    blueprint: &'a Blueprint,
+    objective: usize,
+    time: i32,
+    /// The most of each resource that could ever be spent in a single minute.
+    /// Having more robots than this for a resource is pointless, since the
+    /// factory can only build one robot per minute.
+    max_useful: Vec<i32>,
+    path: Vec<Build>,
+    best: Plan,
+}
+
+impl<'a> Search<'a> {
+    fn new(blueprint: &'a Blueprint, objective: usize, time: i32) -> Self {
+        let mut max_useful = vec![0; blueprint.resources.len()];
+        for recipe in &blueprint.recipes {
+            for (m, c) in max_useful.iter_mut().zip(&recipe.cost) {
+                *m = (*m).max(*c);
+            }
         }
-        if new_bots.obsidian == max_costs.obsidian {
-            new_rsc.obsidian = new_rsc.obsidian.min(max_costs.obsidian);
+        max_useful[objective] = i32::MAX;
+
+        Search {
+            blueprint,
+            objective,
+            time,
+            max_useful,
+            path: vec![],
+            best: Plan {
+                amount: 0,
+                builds: vec![],
+            },
         }
+    }
 
-        State {
-            active_bots: new_bots,
-            resources: new_rsc,
-            minute: self.minute + 1,
-            no_build_build_mask: Resources::default(),
+    /// Minutes of waiting before `recipe` becomes affordable, or `None` if the
+    /// current robots will never collect enough.
+    fn wait_for(recipe: &Recipe, robots: &[i32], stock: &[i32]) -> Option<i32> {
+        let mut wait = 0;
+        for ((&cost, &have), &rate) in recipe.cost.iter().zip(stock).zip(robots) {
+            if cost > have {
+                if rate == 0 {
+                    return None;
+                }
+                wait = wait.max((cost - have + rate - 1) / rate);
+            }
         }
+        Some(wait)
     }
 
-    fn wait(self, max_costs: Resources, build_mask: Resources) -> Self {
-        let mut new_rsc = self.resources + self.active_bots;
-
-        if self.active_bots.ore == max_costs.ore {
-            new_rsc.ore = new_rsc.ore.min(max_costs.ore);
+    // Rather than deciding minute-by-minute, decide which robot to build next
+    // and skip straight ahead to the minute it gets built. This never depends
+    // on the order of the resources: every recipe is just another branch.
+    fn search(&mut self, robots: &mut Vec<i32>, stock: &mut Vec<i32>, time_left: i32) {
+        let obj = self.objective;
+
+        // Doing nothing more is always an option.
+        let idle = stock[obj] + robots[obj] * time_left;
+        if idle > self.best.amount {
+            self.best = Plan {
+                amount: idle,
+                builds: self.path.clone(),
+            };
         }
-        if self.active_bots.clay == max_costs.clay {
-            new_rsc.clay = new_rsc.clay.min(max_costs.clay);
-        }
-        if self.active_bots.obsidian == max_costs.obsidian {
-            new_rsc.obsidian = new_rsc.obsidian.min(max_costs.obsidian);
+
+        // Even building a new objective robot every remaining minute can't beat
+        // what we already have.
+        let optimistic = stock[obj] + robots[obj] * time_left + time_left * (time_left - 1) / 2;
+        if optimistic <= self.best.amount {
+            return;
         }
 
-        State {
-            active_bots: self.active_bots,
-            resources: new_rsc,
-            minute: self.minute + 1,
-            no_build_build_mask: build_mask,
+        // Try objective robots first, since they tighten the bound fastest.
+        let mut order = (0..self.blueprint.recipes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&r| self.blueprint.recipes[r].produces != obj);
+
+        for r in order {
+            let recipe = &self.blueprint.recipes[r];
+            let produces = recipe.produces;
+            if robots[produces] >= self.max_useful[produces] {
+                continue;
+            }
+            let wait = match Self::wait_for(recipe, robots, stock) {
+                // A robot finished in the last minute never collects anything.
+                Some(wait) if wait + 1 < time_left => wait,
+                _ => continue,
+            };
+
+            for i in 0..stock.len() {
+                stock[i] += robots[i] * (wait + 1) - recipe.cost[i];
+            }
+            robots[produces] += 1;
+            self.path.push(Build {
+                minute: self.time - time_left + 1 + wait,
+                recipe: r,
+            });
+
+            self.search(robots, stock, time_left - wait - 1);
+
+            self.path.pop();
+            robots[produces] -= 1;
+            for i in 0..stock.len() {
+                stock[i] -= robots[i] * (wait + 1) - recipe.cost[i];
+            }
         }
     }
 }
 
-fn maximum_geodes(
-    blueprint: Blueprint,
-    active_bots: Resources,
-    resources: Resources,
-    time: u8,
-) -> i32 {
-    let max_costs = Resources {
-        ore: blueprint.all_costs().iter().map(|c| c.ore).max().unwrap(),
-        clay: blueprint.all_costs().iter().map(|c| c.clay).max().unwrap(),
-        obsidian: blueprint
-            .all_costs()
-            .iter()
-            .map(|c| c.obsidian)
-            .max()
-            .unwrap(),
-        geodes: i32::MAX,
-    };
-
-    let mut max_geodes = 0;
-
-    let mut visited = HashSet::new();
-    let mut q = VecDeque::new();
-    q.push_back(State {
-        active_bots,
-        resources,
-        minute: 1,
-        no_build_build_mask: Resources::default(),
-    });
+/// A build order which collects the most of `objective` in `time` minutes,
+/// starting with `robots` and nothing in stock.
+pub fn maximize(blueprint: &Blueprint, objective: usize, robots: &[i32], time: i32) -> Plan {
+    let mut search = Search::new(blueprint, objective, time);
+    search.search(
+        &mut robots.to_vec(),
+        &mut vec![0; blueprint.resources.len()],
+        time,
+    );
+    search.best
+}
 
-    while let Some(
-        state @ State {
-            active_bots,
-            resources,
-            minute,
-            no_build_build_mask,
+pub fn maximum_geodes(blueprint: &Blueprint, time: i32) -> Plan {
+    match blueprint.resource("geode") {
+        Some(geode) => maximize(blueprint, geode, &blueprint.initial_robots(), time),
+        None => Plan {
+            amount: 0,
+            builds: vec![],
         },
-    ) = q.pop_front()
-    {
-        // We visit in BFS order, which means we never observe minutes out-of-order. So we don't
-        // need to include it in the visited set.
-        if visited.contains(&(state.active_bots, state.resources)) {
-            continue;
-        }
-        visited.insert((state.active_bots, state.resources));
+    }
+}
 
-        max_geodes = max_geodes.max((resources + active_bots).geodes);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The build at `minute` needed more than was in stock; `missing` lists
+    /// each resource that fell short and by how much.
+    Shortfall {
+        minute: i32,
+        recipe: usize,
+        missing: Vec<(usize, i32)>,
+    },
+    /// A build was scheduled for a minute in which the factory was already
+    /// busy, or before the previous build.
+    FactoryBusy { minute: i32 },
+    /// A build was scheduled outside of the available time.
+    OutOfTime { minute: i32 },
+    /// The blueprint has no recipe with this index.
+    UnknownRecipe { minute: i32, recipe: usize },
+}
 
-        if minute == time {
-            continue;
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub robots: Vec<i32>,
+    pub stock: Vec<i32>,
+    /// Minute-by-minute account of the replay, in the same words as the
+    /// puzzle's example.
+    pub log: String,
+}
 
-        // If we managed to make a geode machine every cycle, this is the maximum number of geodes
-        // that can be produced:
-        // - new geodes machines: (1+2+3+...+(remaining time))
-        // - existing geodes: remaining time * active geode bots
-        let remaining_time = time as i32 - minute as i32;
-        let max_potential_geodes_remaining =
-            (remaining_time - 1) * remaining_time / 2 + active_bots.geodes * remaining_time;
+fn plural(n: i32, word: &str) -> String {
+    if n == 1 {
+        format!("{n} {word}")
+    } else {
+        format!("{n} {word}s")
+    }
+}
 
-        // If this branch can't produce enough geodes to beat our current max, give up.
-        if max_potential_geodes_remaining + resources.geodes < max_geodes {
-            continue;
+/// Runs `builds` against `blueprint` for `time` minutes, checking that every
+/// build is affordable when it starts.
+pub fn replay(
+    blueprint: &Blueprint,
+    robots: &[i32],
+    builds: &[Build],
+    time: i32,
+) -> Result<Replay, ReplayError> {
+    let mut robots = robots.to_vec();
+    let mut stock = vec![0; blueprint.resources.len()];
+    let mut log = String::new();
+    let mut builds = builds.iter().peekable();
+
+    for minute in 1..=time {
+        if minute > 1 {
+            log.push('\n');
         }
+        writeln!(log, "== Minute {minute} ==").unwrap();
+
+        let mut building = None;
+        if let Some(build) = builds.next_if(|b| b.minute <= minute) {
+            if build.minute < 1 {
+                return Err(ReplayError::OutOfTime {
+                    minute: build.minute,
+                });
+            } else if build.minute < minute {
+                return Err(ReplayError::FactoryBusy {
+                    minute: build.minute,
+                });
+            }
+            let recipe = blueprint
+                .recipes
+                .get(build.recipe)
+                .ok_or(ReplayError::UnknownRecipe {
+                    minute,
+                    recipe: build.recipe,
+                })?;
+
+            let missing = recipe
+                .cost
+                .iter()
+                .zip(&stock)
+                .enumerate()
+                .filter(|(_, (cost, have))| cost > have)
+                .map(|(r, (cost, have))| (r, cost - have))
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return Err(ReplayError::Shortfall {
+                    minute,
+                    recipe: build.recipe,
+                    missing,
+                });
+            }
 
-        // If we waited a cycle when we _could_ have built something, don't try to build it this
-        // cycle -- we also explored that branch one minute ago, and that branch is strictly better
-        // in all cases.
-        let build_mask = Resources {
-            geodes: resources.can_build(blueprint.geode_bot_cost).into(),
-            obsidian: resources.can_build(blueprint.obsidian_bot_cost).into(),
-            clay: resources.can_build(blueprint.clay_bot_cost).into(),
-            ore: resources.can_build(blueprint.ore_bot_cost).into(),
-        } - no_build_build_mask;
-
-        // Always prefer to build geode bots, if possible.
-        if build_mask.geodes > 0 {
-            q.push_back(state.build_bot(
-                Resources::geodes_unit(),
-                blueprint.geode_bot_cost,
-                max_costs,
-            ));
-            continue;
+            let spent = recipe
+                .cost
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c > 0)
+                .map(|(r, c)| format!("{c} {}", blueprint.resources[r]))
+                .collect::<Vec<_>>()
+                .join(" and ");
+            let name = blueprint.robot_name(recipe.produces);
+            let article = if name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+                "an"
+            } else {
+                "a"
+            };
+            writeln!(log, "Spend {spent} to start building {article} {name}.").unwrap();
+
+            for (s, c) in stock.iter_mut().zip(&recipe.cost) {
+                *s -= c;
+            }
+            building = Some(recipe.produces);
         }
 
-        // Or obsidian bots, if we can, and we haven't maxed out the value
-        if active_bots.obsidian < max_costs.obsidian && build_mask.obsidian > 0 {
-            q.push_back(state.build_bot(
-                Resources::obsidian_unit(),
-                blueprint.obsidian_bot_cost,
-                max_costs,
-            ));
-        }
-        // Or clay bots, if we can, and we haven't maxed out the value
-        if active_bots.clay < max_costs.clay && build_mask.clay > 0 {
-            q.push_back(state.build_bot(
-                Resources::clay_unit(),
-                blueprint.clay_bot_cost,
-                max_costs,
-            ));
-        }
-        // Or ore bots, if we can, and we haven't maxed out the value
-        if active_bots.ore < max_costs.ore && build_mask.ore > 0 {
-            q.push_back(state.build_bot(Resources::ore_unit(), blueprint.ore_bot_cost, max_costs));
+        for (r, &n) in robots.iter().enumerate() {
+            if n == 0 {
+                continue;
+            }
+            stock[r] += n;
+            let name = &blueprint.resources[r];
+            let line = if name == "geode" {
+                format!(
+                    "{} {} {}; you now have {} open {}.",
+                    plural(n, "geode-cracking robot"),
+                    if n == 1 { "cracks" } else { "crack" },
+                    plural(n, "geode"),
+                    stock[r],
+                    if stock[r] == 1 { "geode" } else { "geodes" },
+                )
+            } else {
+                format!(
+                    "{} {} {n} {name}; you now have {} {name}.",
+                    plural(n, &format!("{name}-collecting robot")),
+                    if n == 1 { "collects" } else { "collect" },
+                    stock[r],
+                )
+            };
+            writeln!(log, "{line}").unwrap();
         }
 
-        // If we can already build every type of bot, no need to wait for resources ever.
-        if build_mask.obsidian > 0 && build_mask.clay > 0 && build_mask.ore > 0 {
-            continue;
+        if let Some(produces) = building {
+            robots[produces] += 1;
+            writeln!(
+                log,
+                "The new {} is ready; you now have {} of them.",
+                blueprint.robot_name(produces),
+                robots[produces]
+            )
+            .unwrap();
         }
+    }
 
-        q.push_back(state.wait(max_costs, build_mask));
+    if let Some(build) = builds.next() {
+        return Err(ReplayError::OutOfTime {
+            minute: build.minute,
+        });
     }
 
-    max_geodes
+    Ok(Replay { robots, stock, log })
 }
 
 pub fn part_1(input: &str) -> i32 {
-    let blueprints = parse(input);
-
-    let mut quality_sum = 0;
-    for blueprint in blueprints {
-        let resources = Resources::default();
-        let active_bots = Resources {
-            ore: 1,
-            ..Default::default()
-        };
-        let max_geodes = maximum_geodes(blueprint, active_bots, resources, 24);
+    part_1_with(input, Parallelism::from_env())
+}
 
-        quality_sum += blueprint.id * max_geodes;
-    }
+pub fn part_1_with(input: &str, parallelism: Parallelism) -> i32 {
+    let blueprints = parse(input);
 
-    quality_sum
+    let max_geodes = parallelism.map(&blueprints, |b| maximum_geodes(b, 24).amount);
+    blueprints
+        .iter()
+        .zip(max_geodes)
+        .map(|(blueprint, max_geodes)| blueprint.id * max_geodes)
+        .sum()
 }
 
 pub fn part_2(input: &str) -> i32 {
-    let blueprints = parse(input);
+    part_2_with(input, Parallelism::from_env())
+}
 
-    let mut p = 1;
-    for blueprint in blueprints.iter().take(3) {
-        let resources = Resources::default();
-        let active_bots = Resources {
-            ore: 1,
-            ..Default::default()
-        };
-        let max_geodes = maximum_geodes(*blueprint, active_bots, resources, 32);
-        p *= max_geodes;
-    }
-    p
+pub fn part_2_with(input: &str, parallelism: Parallelism) -> i32 {
+    let mut blueprints = parse(input);
+    blueprints.truncate(3);
+
+    parallelism
+        .map(&blueprints, |b| maximum_geodes(b, 32).amount)
+        .into_iter()
+        .product()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_19::{part_1, part_2};
+    use crate::day_19::{
+        maximize, maximum_geodes, parse, part_1, part_1_with, part_2, part_2_with, replay, Build,
+        ReplayError,
+    };
+    use crate::parallel::Parallelism;
 
     const INPUTS: &str = r#"Blueprint 1: Each ore robot costs 4 ore.  Each clay robot costs 2 ore.  Each obsidian robot costs 3 ore and 14 clay.  Each geode robot costs 2 ore and 7 obsidian.
 Blueprint 2: Each ore robot costs 2 ore.  Each clay robot costs 3 ore.  Each obsidian robot costs 3 ore and 8 clay.  Each geode robot costs 3 ore and 12 obsidian."#;
@@ -470,4 +567,135 @@ Blueprint 2: Each ore robot costs 2 ore.  Each clay robot costs 3 ore.  Each obs
     pub fn test_day_19_part2() {
         assert_eq!(part_2(include_str!("input/day_19.txt")), 21840);
     }
+
+    #[test]
+    pub fn test_day_19_parse_resources() {
+        let blueprints = parse(INPUTS);
+        assert_eq!(
+            blueprints[0].resources,
+            vec!["ore", "clay", "obsidian", "geode"]
+        );
+        assert_eq!(blueprints[0].recipes[2].produces, 2);
+        assert_eq!(blueprints[0].recipes[2].cost, vec![3, 14, 0, 0]);
+        assert_eq!(blueprints[1].id, 2);
+    }
+
+    #[test]
+    pub fn test_day_19_other_objectives() {
+        let blueprint = &parse(INPUTS)[0];
+        let robots = blueprint.initial_robots();
+
+        // Nothing to build: the single ore robot collects one ore a minute.
+        assert_eq!(maximize(blueprint, 0, &robots, 1).amount, 1);
+        // One clay robot after 2 minutes, which then collects for one minute.
+        assert_eq!(maximize(blueprint, 1, &robots, 4).amount, 1);
+        assert_eq!(maximize(blueprint, 3, &robots, 24).amount, 9);
+    }
+
+    #[test]
+    pub fn test_day_19_custom_resources() {
+        let blueprints = parse(
+            "Blueprint 7: Each ore robot costs 2 ore. Each sand robot costs 1 ore. \
+             Each glass robot costs 3 sand and 1 ore.",
+        );
+        let blueprint = &blueprints[0];
+        assert_eq!(blueprint.resources, vec!["ore", "sand", "glass"]);
+
+        let glass = blueprint.resource("glass").unwrap();
+        let robots = blueprint.initial_robots();
+        assert_eq!(maximize(blueprint, glass, &robots, 5).amount, 0);
+        assert!(maximize(blueprint, glass, &robots, 12).amount > 0);
+    }
+
+    #[test]
+    pub fn test_day_19_example_build_order() {
+        let blueprint = &parse(INPUTS)[0];
+        let plan = maximum_geodes(blueprint, 24);
+        assert_eq!(plan.amount, 9);
+
+        let result = replay(blueprint, &blueprint.initial_robots(), &plan.builds, 24).unwrap();
+        assert_eq!(result.stock[3], 9);
+        assert!(result.log.starts_with(
+            "== Minute 1 ==\n1 ore-collecting robot collects 1 ore; you now have 1 ore.\n\n"
+        ));
+        assert!(result.log.contains("; you now have 9 open geodes.\n"));
+    }
+
+    #[test]
+    pub fn test_day_19_replay_example_narrative() {
+        let blueprint = &parse(INPUTS)[0];
+        // The build order from the puzzle's walkthrough.
+        let builds = [
+            (3, 1),
+            (5, 1),
+            (7, 1),
+            (11, 2),
+            (12, 1),
+            (15, 2),
+            (18, 3),
+            (21, 3),
+        ]
+        .map(|(minute, recipe)| Build { minute, recipe });
+
+        let result = replay(blueprint, &blueprint.initial_robots(), &builds, 24).unwrap();
+        assert_eq!(result.robots, vec![1, 4, 2, 2]);
+        assert_eq!(result.stock, vec![6, 41, 8, 9]);
+        assert!(result.log.contains(
+            "== Minute 3 ==\n\
+             Spend 2 ore to start building a clay-collecting robot.\n\
+             1 ore-collecting robot collects 1 ore; you now have 1 ore.\n\
+             The new clay-collecting robot is ready; you now have 1 of them.\n"
+        ));
+        assert!(result.log.contains(
+            "== Minute 11 ==\n\
+             Spend 3 ore and 14 clay to start building an obsidian-collecting robot.\n"
+        ));
+        assert!(result.log.ends_with(
+            "== Minute 24 ==\n\
+             1 ore-collecting robot collects 1 ore; you now have 6 ore.\n\
+             4 clay-collecting robots collect 4 clay; you now have 41 clay.\n\
+             2 obsidian-collecting robots collect 2 obsidian; you now have 8 obsidian.\n\
+             2 geode-cracking robots crack 2 geodes; you now have 9 open geodes.\n"
+        ));
+    }
+
+    #[test]
+    pub fn test_day_19_replay_errors() {
+        let blueprint = &parse(INPUTS)[0];
+        let robots = blueprint.initial_robots();
+        let build = |minute, recipe| Build { minute, recipe };
+
+        assert_eq!(
+            replay(blueprint, &robots, &[build(2, 2)], 24),
+            Err(ReplayError::Shortfall {
+                minute: 2,
+                recipe: 2,
+                missing: vec![(0, 2), (1, 14)],
+            })
+        );
+        assert_eq!(
+            replay(blueprint, &robots, &[build(3, 1), build(3, 1)], 24),
+            Err(ReplayError::FactoryBusy { minute: 3 })
+        );
+        assert_eq!(
+            replay(blueprint, &robots, &[build(25, 0)], 24),
+            Err(ReplayError::OutOfTime { minute: 25 })
+        );
+        assert_eq!(
+            replay(blueprint, &robots, &[build(3, 1), build(5, 4)], 24),
+            Err(ReplayError::UnknownRecipe {
+                minute: 5,
+                recipe: 4
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_day_19_sequential_matches_parallel() {
+        let input = include_str!("input/day_19.txt");
+        for p in [Parallelism::Sequential, Parallelism::Threads(4)] {
+            assert_eq!(part_1_with(input, p), 1349);
+            assert_eq!(part_2_with(input, p), 21840);
+        }
+    }
 }
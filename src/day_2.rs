@@ -81,7 +81,6 @@
 ///
 /// Following the Elf's instructions for the second column, what would your
 /// total score be if everything goes exactly according to your strategy guide?
-
 pub fn part_1(inputs: &str) -> u64 {
     let mut total_score = 0;
     for line in inputs.split('\n') {
@@ -102,46 +102,329 @@
 // Apply the decryption key and mix your encrypted file ten times. What is the
 // sum of the three numbers that form the grove coordinates?
 
-fn decrypt(nums: &[i64], iterations: usize, decryption_key: i64) -> i64 {
-    let nums = nums.iter().map(|x| x * decryption_key).collect::<Vec<_>>();
-    let len = nums.len();
-    let ilen = len as i64;
+const NIL: usize = usize::MAX;
+
+/// An implicit treap over the indices `0..n`, ordered by their current
+/// position in the file. Each node keeps a parent pointer, so the position of
+/// a given index can be found by walking up to the root.
+struct Treap {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    priority: Vec<u64>,
+    root: usize,
+}
+
+impl Treap {
+    fn new(n: usize) -> Self {
+        // Any well-mixed sequence will do for the priorities; xorshift keeps
+        // the shape of the tree deterministic.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let priority = (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect();
+
+        let mut treap = Treap {
+            left: vec![NIL; n],
+            right: vec![NIL; n],
+            parent: vec![NIL; n],
+            size: vec![1; n],
+            priority,
+            root: NIL,
+        };
+        for i in 0..n {
+            treap.root = treap.merge(treap.root, i);
+        }
+        if treap.root != NIL {
+            treap.parent[treap.root] = NIL;
+        }
+        treap
+    }
+
+    fn len(&self, n: usize) -> usize {
+        if n == NIL {
+            0
+        } else {
+            self.size[n]
+        }
+    }
+
+    fn update(&mut self, n: usize) {
+        let (l, r) = (self.left[n], self.right[n]);
+        self.size[n] = 1 + self.len(l) + self.len(r);
+        if l != NIL {
+            self.parent[l] = n;
+        }
+        if r != NIL {
+            self.parent[r] = n;
+        }
+    }
+
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.priority[a] > self.priority[b] {
+            let r = self.merge(self.right[a], b);
+            self.right[a] = r;
+            self.update(a);
+            a
+        } else {
+            let l = self.merge(a, self.left[b]);
+            self.left[b] = l;
+            self.update(b);
+            b
+        }
+    }
+
+    /// Splits off the first `k` nodes of the subtree at `n`.
+    fn split(&mut self, n: usize, k: usize) -> (usize, usize) {
+        if n == NIL {
+            return (NIL, NIL);
+        }
+        let l = self.left[n];
+        if self.len(l) >= k {
+            let (a, b) = self.split(l, k);
+            self.left[n] = b;
+            self.update(n);
+            (a, n)
+        } else {
+            let (a, b) = self.split(self.right[n], k - self.len(l) - 1);
+            self.right[n] = a;
+            self.update(n);
+            (n, b)
+        }
+    }
+
+    /// The current position of index `i`.
+    fn position(&self, i: usize) -> usize {
+        let mut pos = self.len(self.left[i]);
+        let mut n = i;
+        while self.parent[n] != NIL {
+            let p = self.parent[n];
+            if self.right[p] == n {
+                pos += self.len(self.left[p]) + 1;
+            }
+            n = p;
+        }
+        pos
+    }
+
+    fn move_to(&mut self, i: usize, new_pos: usize) {
+        let pos = self.position(i);
+        let (a, b) = self.split(self.root, pos);
+        let (node, c) = self.split(b, 1);
+        debug_assert_eq!(node, i);
+        let rest = self.merge(a, c);
+
+        let (a, b) = self.split(rest, new_pos);
+        let a = self.merge(a, node);
+        self.root = self.merge(a, b);
+        self.parent[self.root] = NIL;
+    }
+
+    fn to_vec(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.size.len());
+        let mut stk = vec![];
+        let mut n = self.root;
+        while n != NIL || !stk.is_empty() {
+            while n != NIL {
+                stk.push(n);
+                n = self.left[n];
+            }
+            n = stk.pop().unwrap();
+            order.push(n);
+            n = self.right[n];
+        }
+        order
+    }
+}
+
+/// Moves the number originally at index `i` of `nums` as far as its value.
+fn move_number(treap: &mut Treap, nums: &[i64], i: usize) {
+    if nums.len() < 2 {
+        return;
+    }
+    let pos = treap.position(i);
+    let len = nums.len() as i64 - 1;
+    // Reduced first, so numbers near the ends of the range don't overflow.
+    let new_i = (pos as i64 + nums[i].rem_euclid(len)).rem_euclid(len) as usize;
+    treap.move_to(i, new_i);
+}
+
+/// Mixes `nums` for `rounds` rounds, returning the original indices in their
+/// final order. Each move is O(log n), so this is fine for millions of
+/// numbers.
+fn mix(nums: &[i64], rounds: usize) -> Vec<usize> {
+    let mut treap = Treap::new(nums.len());
+
+    for _ in 0..rounds {
+        for i in 0..nums.len() {
+            move_number(&mut treap, nums, i);
+        }
+    }
+
+    treap.to_vec()
+}
+
+/// The original quadratic mixing, kept as a reference for [`mix`].
+#[cfg(test)]
+fn mix_naive(nums: &[i64], rounds: usize) -> Vec<usize> {
+    let ilen = nums.len() as i128;
     let mut indices = (0..nums.len()).collect::<Vec<_>>();
 
-    for _ in 0..iterations {
+    for _ in 0..rounds {
         for (i, &x) in nums.iter().enumerate() {
             let pos = indices.iter().position(|&y| y == i).unwrap();
             indices.remove(pos);
-            let new_i = (pos as i64 + x).rem_euclid(ilen - 1) as usize;
+            let new_i = (pos as i128 + x as i128).rem_euclid(ilen - 1) as usize;
             indices.insert(new_i, i);
         }
     }
 
-    let z_i = indices.iter().position(|&i| nums[i] == 0).unwrap();
-    nums[indices[(z_i + 1000) % len]]
-        + nums[indices[(z_i + 2000) % len]]
-        + nums[indices[(z_i + 3000) % len]]
+    indices
 }
 
-pub fn part_1(input: &str) -> i64 {
-    let file: Vec<i64> = input
+pub const DECRYPTION_KEY: i64 = 811589153;
+
+pub fn parse(input: &str) -> Vec<i64> {
+    input
         .split_whitespace()
         .map(|l| l.parse().unwrap())
-        .collect();
-    decrypt(&file, 1, 1)
+        .collect()
+}
+
+/// How to mix a file and read the grove coordinates back out of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decryption {
+    /// Every number is multiplied by this before mixing.
+    pub key: i64,
+    pub rounds: usize,
+    /// The value the offsets are counted from, as it is in the file before
+    /// the key is applied.
+    pub anchor: i64,
+    pub offsets: Vec<usize>,
+}
+
+impl Decryption {
+    pub fn part_1() -> Self {
+        Decryption {
+            key: 1,
+            rounds: 1,
+            anchor: 0,
+            offsets: vec![1000, 2000, 3000],
+        }
+    }
+
+    pub fn part_2() -> Self {
+        Decryption {
+            key: DECRYPTION_KEY,
+            rounds: 10,
+            ..Decryption::part_1()
+        }
+    }
+
+    /// `None` if applying the key overflows.
+    fn apply_key(&self, nums: &[i64]) -> Option<Vec<i64>> {
+        nums.iter().map(|x| x.checked_mul(self.key)).collect()
+    }
+
+    /// The file after mixing, with the key applied. The list is circular, so
+    /// only the order matters and not where it starts. `None` if applying
+    /// the key overflows.
+    pub fn mixed(&self, nums: &[i64]) -> Option<Vec<i64>> {
+        let nums = self.apply_key(nums)?;
+        Some(
+            mix(&nums, self.rounds)
+                .into_iter()
+                .map(|i| nums[i])
+                .collect(),
+        )
+    }
+
+    /// The numbers at each offset after the first occurrence of the anchor,
+    /// or `None` if the anchor isn't in the mixed file or applying the key
+    /// overflows.
+    pub fn grove_coordinates(&self, nums: &[i64]) -> Option<Vec<i64>> {
+        let mixed = self.mixed(nums)?;
+        let anchor = self.anchor.checked_mul(self.key)?;
+        let a_i = mixed.iter().position(|&x| x == anchor)?;
+        Some(
+            self.offsets
+                .iter()
+                .map(|o| mixed[(a_i + o) % mixed.len()])
+                .collect(),
+        )
+    }
+
+    /// The sum of the grove coordinates, or `None` if there aren't any
+    /// coordinates or the numbers overflow.
+    pub fn decrypt(&self, nums: &[i64]) -> Option<i64> {
+        self.grove_coordinates(nums)?
+            .into_iter()
+            .try_fold(0i64, |acc, x| acc.checked_add(x))
+    }
+
+    /// Steps through the mixing one move at a time, yielding the whole file
+    /// after each move. `None` if applying the key overflows.
+    pub fn moves(&self, nums: &[i64]) -> Option<Moves> {
+        Some(Moves {
+            treap: Treap::new(nums.len()),
+            nums: self.apply_key(nums)?,
+            remaining: self.rounds * nums.len(),
+            next: 0,
+        })
+    }
+}
+
+pub struct Moves {
+    treap: Treap,
+    nums: Vec<i64>,
+    remaining: usize,
+    next: usize,
+}
+
+impl Iterator for Moves {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        move_number(&mut self.treap, &self.nums, self.next);
+        self.next = (self.next + 1) % self.nums.len();
+        self.remaining -= 1;
+
+        Some(
+            self.treap
+                .to_vec()
+                .into_iter()
+                .map(|i| self.nums[i])
+                .collect(),
+        )
+    }
+}
+
+pub fn part_1(input: &str) -> i64 {
+    Decryption::part_1().decrypt(&parse(input)).unwrap()
 }
 
 pub fn part_2(input: &str) -> i64 {
-    let file: Vec<i64> = input
-        .split_whitespace()
-        .map(|l| l.parse().unwrap())
-        .collect();
-    decrypt(&file, 10, 811589153)
+    Decryption::part_2().decrypt(&parse(input)).unwrap()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_20::{part_1, part_2};
+    use crate::day_20::{mix, mix_naive, parse, part_1, part_2, Decryption};
 
     const INPUTS: &str = r#"1
 2
@@ -169,4 +452,125 @@ pub mod tests {
     pub fn test_day_20_part2() {
         assert_eq!(part_2(include_str!("input/day_20.txt")), 8332585833851);
     }
+
+    #[test]
+    pub fn test_day_20_mix_matches_naive() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for len in [2, 3, 7, 50, 500] {
+            let nums = (0..len)
+                .map(|_| (next() % 20001) as i64 - 10000)
+                .collect::<Vec<_>>();
+            for rounds in [1, 3] {
+                assert_eq!(mix(&nums, rounds), mix_naive(&nums, rounds));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_20_mix_large() {
+        let nums = (0..200_000)
+            .map(|i| (i * 7919) % 10007 - 5003)
+            .collect::<Vec<i64>>();
+        let mut order = mix(&nums, 1);
+        order.sort();
+        assert_eq!(order, (0..nums.len()).collect::<Vec<_>>());
+    }
+
+    /// Rotates a circular list to start at `first`, so that arrangements can
+    /// be compared regardless of where they happen to begin.
+    fn starting_at(list: Vec<i64>, first: i64) -> Vec<i64> {
+        let mut list = list;
+        let pos = list.iter().position(|&x| x == first).unwrap();
+        list.rotate_left(pos);
+        list
+    }
+
+    #[test]
+    pub fn test_day_20_example_moves() {
+        let nums = parse(INPUTS);
+        let moves = Decryption::part_1()
+            .moves(&nums)
+            .unwrap()
+            .map(|m| starting_at(m, 1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            moves,
+            vec![
+                vec![1, -3, 3, -2, 0, 4, 2],
+                vec![1, -3, 2, 3, -2, 0, 4],
+                vec![1, 2, 3, -2, -3, 0, 4],
+                vec![1, 2, -2, -3, 0, 3, 4],
+                vec![1, 2, -3, 0, 3, 4, -2],
+                vec![1, 2, -3, 0, 3, 4, -2],
+                vec![1, 2, -3, 4, 0, 3, -2],
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_day_20_example_configurable() {
+        let nums = parse(INPUTS);
+        let decryption = Decryption::part_1();
+        assert_eq!(
+            starting_at(decryption.mixed(&nums).unwrap(), 0),
+            vec![0, 3, -2, 1, 2, -3, 4]
+        );
+        assert_eq!(decryption.grove_coordinates(&nums), Some(vec![4, -3, 2]));
+
+        let custom = Decryption {
+            anchor: 3,
+            offsets: vec![1, 2],
+            ..Decryption::part_1()
+        };
+        assert_eq!(custom.grove_coordinates(&nums), Some(vec![-2, 1]));
+        assert_eq!(custom.decrypt(&nums), Some(-1));
+
+        let missing = Decryption {
+            anchor: 5,
+            ..Decryption::part_1()
+        };
+        assert_eq!(missing.decrypt(&nums), None);
+
+        let part_2 = Decryption::part_2();
+        assert_eq!(
+            part_2.grove_coordinates(&nums),
+            Some(vec![811589153, 2434767459, -1623178306])
+        );
+        assert_eq!(part_2.moves(&nums).unwrap().count(), 70);
+
+        // 4 * (i64::MAX / 2) doesn't fit, whether in the file or the anchor.
+        let huge = Decryption {
+            key: i64::MAX / 2,
+            ..Decryption::part_1()
+        };
+        assert_eq!(huge.mixed(&nums), None);
+        assert!(huge.moves(&nums).is_none());
+        assert_eq!(huge.decrypt(&nums), None);
+        let huge_anchor = Decryption {
+            anchor: 4,
+            ..huge.clone()
+        };
+        assert_eq!(huge_anchor.grove_coordinates(&[0, 1]), None);
+
+        // Keys close to overflowing still mix correctly.
+        let big = Decryption {
+            key: i64::MAX / 4,
+            ..Decryption::part_1()
+        };
+        let keyed = nums.iter().map(|x| x * big.key).collect::<Vec<_>>();
+        assert_eq!(
+            big.mixed(&nums).map(|m| starting_at(m, 0)),
+            Some(starting_at(
+                mix_naive(&keyed, 1).into_iter().map(|i| keyed[i]).collect(),
+                0
+            ))
+        );
+    }
 }
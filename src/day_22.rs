@@ -280,260 +280,572 @@ struct Face {
     grid: Vec<Vec<bool>>,
 }
 
-struct State<'a, 'b> {
-    face: FaceId,
-    faces: &'a [Face],
-    cube_len: isize,
-    pos: (usize, usize),
-    dir: Dir,
-    history: &'b mut Vec<Vec<char>>,
+/// Something wrong with the shape of the map which stops it from being folded
+/// into a cube.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetError {
+    /// There are no open or wall tiles at all.
+    Empty,
+    /// The number of tiles isn't six times a square number.
+    Area(usize),
+    /// The tiles don't split into square faces aligned with each other; the
+    /// position is the first tile that doesn't fit.
+    Misaligned((usize, usize)),
+    /// The faces don't all connect, or there are more or fewer than six.
+    FaceCount(usize),
+    /// Folding puts both faces in the same place on the cube.
+    Overlap(FaceId, FaceId),
 }
 
-impl<'a, 'b> State<'a, 'b> {
-    fn orig_pos(&self) -> (usize, usize) {
-        let d = self.faces[self.face as usize].top_left;
-        (self.pos.0 + d.0, self.pos.1 + d.1)
+/// Where walking off the edge of a face leads: onto `face`, now facing `dir`.
+/// When `flipped` is set the coordinate along the edge is mirrored as well as
+/// rotated, which is what makes a surface non-orientable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub face: FaceId,
+    pub dir: Dir,
+    pub flipped: bool,
+}
+
+/// How the edges of the faces connect up. Returning `None` makes the edge a
+/// boundary which can't be crossed, as though it were a wall.
+pub trait Topology {
+    fn cross(&self, face: FaceId, dir: Dir) -> Option<Edge>;
+}
+
+/// A table of where walking off each edge of each face leads, indexed by face
+/// and then by the direction you walk off in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gluing {
+    table: [[Option<Edge>; 4]; 6],
+}
+
+impl Gluing {
+    pub fn new(table: [[Option<Edge>; 4]; 6]) -> Self {
+        Gluing { table }
     }
 
-    fn blocked(&self, face: FaceId, pos: (usize, usize)) -> bool {
-        self.faces[face as usize].grid[pos.0][pos.1]
+    pub fn get(&self, face: FaceId, dir: Dir) -> Option<Edge> {
+        self.table[face as usize][dir as usize]
     }
 
-    fn answer(&self) -> usize {
-        let pos = self.orig_pos();
-        1000 * (pos.0 + 1) + 4 * (pos.1 + 1) + self.dir as usize
+    /// Reads a table in the same format that it is displayed in: one line per
+    /// edge, like `A R -> B D`, `A R -> B D flipped` or `A U -> -` for a
+    /// boundary. Edges which aren't listed are boundaries.
+    pub fn parse(input: &str) -> Option<Gluing> {
+        let face = |s: &str| ["A", "B", "C", "X", "Y", "Z"].iter().position(|f| *f == s);
+        let dir = |s: &str| ["R", "D", "L", "U"].iter().position(|d| *d == s);
+
+        let mut table = [[None; 4]; 6];
+        for line in input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let (from, to) = line.split_once(" -> ")?;
+            let (from_face, from_dir) = from.split_once(' ')?;
+            let entry = &mut table[face(from_face)?][dir(from_dir)?];
+
+            let mut to = to.split_whitespace();
+            *entry = match (to.next()?, to.next(), to.next()) {
+                ("-", None, None) => None,
+                (to_face, Some(to_dir), flipped) => Some(Edge {
+                    face: face(to_face)?.into(),
+                    dir: dir(to_dir)?.into(),
+                    flipped: match flipped {
+                        None => false,
+                        Some("flipped") => true,
+                        Some(_) => return None,
+                    },
+                }),
+                _ => return None,
+            };
+        }
+        Some(Gluing { table })
     }
+}
 
-    fn update(&mut self, new_face: FaceId, new_pos: (usize, usize), new_dir: Dir) {
-        self.face = new_face;
-        self.dir = new_dir;
-        self.pos = new_pos;
+impl Topology for Gluing {
+    fn cross(&self, face: FaceId, dir: Dir) -> Option<Edge> {
+        self.get(face, dir)
+    }
+}
 
-        let o = self.orig_pos();
-        self.history[o.0][o.1] = new_dir.sym();
+impl std::fmt::Display for Gluing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (face, row) in self.table.iter().enumerate() {
+            for (dir, edge) in row.iter().enumerate() {
+                write!(f, "{:?} {:?} -> ", FaceId::from(face), Dir::from(dir))?;
+                match edge {
+                    Some(e) if e.flipped => writeln!(f, "{:?} {:?} flipped", e.face, e.dir)?,
+                    Some(e) => writeln!(f, "{:?} {:?}", e.face, e.dir)?,
+                    None => writeln!(f, "-")?,
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-fn solve(
-    i2: &str,
-    faces: &[Face],
-    map: &mut Vec<Vec<char>>,
-    face_lookup: &[Vec<usize>],
+/// What happens when walking off the edge of the whole map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Wrap {
+    /// Come back in on the opposite side.
+    Around,
+    /// Come back in on the opposite side, mirrored across the middle of the
+    /// map.
+    Mirrored,
+    /// Stop at the edge.
+    Boundary,
+}
+
+/// The map, split into its six faces.
+pub struct Board {
+    faces: Vec<Face>,
+    map: Vec<Vec<char>>,
+    face_lookup: Vec<Vec<usize>>,
     cube_len: usize,
-    traverse: impl Fn(FaceId, Dir) -> (FaceId, Dir),
-) -> usize {
-    let mut state = State {
-        faces,
-        face: FaceId::A,
-        pos: (0, 0),
-        dir: Dir::R,
-        history: map,
-        cube_len: cube_len as isize,
-    };
+}
 
-    let mut dist = 0;
-    for c in i2.trim().chars() {
-        let d = if let Some(v) = c.to_digit(10) {
-            dist = dist * 10 + v;
-            0
-        } else if c == 'L' || c == 'R' {
-            let v = dist;
-            dist = 0;
-            v
-        } else {
-            unreachable!("'{:?}'", c);
-        };
+impl Board {
+    /// Splits the map into faces, inferring their size from the number of
+    /// tiles.
+    pub fn parse(i1: &str) -> Result<Board, NetError> {
+        let max_cols = i1.lines().map(|l| l.len()).max().unwrap_or(0);
+        let max_rows = i1.lines().count();
+
+        let mut map = vec![vec![' '; max_cols]; max_rows];
+        for (r, l) in i1.lines().enumerate() {
+            for (c, x) in l.chars().enumerate() {
+                map[r][c] = x;
+            }
+        }
 
-        mv(&mut state, &traverse, d);
+        let area = map.iter().flatten().filter(|c| **c != ' ').count();
+        if area == 0 {
+            return Err(NetError::Empty);
+        }
+        let cube_len = (1..).find(|l| 6 * l * l >= area).unwrap();
+        if 6 * cube_len * cube_len != area {
+            return Err(NetError::Area(area));
+        }
 
-        if c == 'L' {
-            state.dir = state.dir.turn_left();
-        } else if c == 'R' {
-            state.dir = state.dir.turn_right();
+        // Find all six faces of the cube
+        let mut face_lookup = vec![vec![9usize; max_cols]; max_rows];
+        let mut face_id = 0;
+
+        let mut faces = vec![];
+        // The first tile in reading order; there is one, since the area isn't
+        // zero. Faces are aligned to the top left of the map, even when that
+        // starts with blank lines.
+        let start = map
+            .iter()
+            .enumerate()
+            .find_map(|(r, row)| Some((r, row.iter().position(|c| *c != ' ')?)))
+            .ok_or(NetError::Empty)?;
+        if start.0 % cube_len != 0 || start.1 % cube_len != 0 {
+            return Err(NetError::Misaligned(start));
         }
-    }
-    mv(&mut state, &traverse, dist);
-
-    println!();
-    for l in state.history.iter() {
-        for x in l.iter().copied() {
-            eprint!(
-                "{}",
-                if x == '.' {
-                    ' '
-                } else if x == ' ' {
-                    '.'
-                } else {
-                    x
+        let mut stk = vec![start];
+
+        while let Some(top_left) = stk.pop() {
+            if map[top_left.0][top_left.1] != ' ' && face_lookup[top_left.0][top_left.1] == 9 {
+                let mut grid = vec![vec![false; cube_len]; cube_len];
+
+                for r in top_left.0..top_left.0 + cube_len {
+                    for c in top_left.1..top_left.1 + cube_len {
+                        if r >= max_rows || c >= max_cols || map[r][c] == ' ' {
+                            return Err(NetError::Misaligned((r, c)));
+                        }
+                        face_lookup[r][c] = face_id;
+                        grid[r - top_left.0][c - top_left.1] = map[r][c] == '#';
+                    }
                 }
-            );
+                faces.push(Face { top_left, grid });
+                face_id += 1;
+                if top_left.0 >= cube_len && face_lookup[top_left.0 - cube_len][top_left.1] == 9 {
+                    stk.push((top_left.0 - cube_len, top_left.1));
+                }
+                if top_left.0 + cube_len < map.len()
+                    && face_lookup[top_left.0 + cube_len][top_left.1] == 9
+                {
+                    stk.push((top_left.0 + cube_len, top_left.1));
+                }
+                if top_left.1 >= cube_len && face_lookup[top_left.0][top_left.1 - cube_len] == 9 {
+                    stk.push((top_left.0, top_left.1 - cube_len));
+                }
+                if top_left.1 + cube_len < map[top_left.0].len()
+                    && face_lookup[top_left.0][top_left.1 + cube_len] == 9
+                {
+                    stk.push((top_left.0, top_left.1 + cube_len));
+                }
+            }
         }
-        println!();
-    }
-    println!();
 
-    println!();
-    for (_i, r) in face_lookup.iter().enumerate() {
-        for (_j, x) in r.iter().enumerate() {
-            if *x == 9 {
-                eprint!(" ");
-            } else {
-                eprint!("{:?}", FaceId::from(*x));
+        if faces.len() != 6 {
+            return Err(NetError::FaceCount(faces.len()));
+        }
+        for (r, row) in map.iter().enumerate() {
+            for (c, x) in row.iter().enumerate() {
+                if *x != ' ' && face_lookup[r][c] == 9 {
+                    return Err(NetError::Misaligned((r, c)));
+                }
             }
         }
 
-        println!();
+        Ok(Board {
+            faces,
+            map,
+            face_lookup,
+            cube_len,
+        })
     }
-    println!();
 
-    state.answer()
-}
+    pub fn cube_len(&self) -> usize {
+        self.cube_len
+    }
 
-pub fn part_1(input: &str, cube_len: usize) -> usize {
-    let (i1, i2) = input.split_once("\n\n").unwrap();
-    let (faces, mut map, face_lookup) = extract_faces(i1, cube_len);
-    let t = |face_id, dir| {
-        let c = faces[face_id as usize].top_left;
-        let p = |v, x, l| (v as isize + x as isize).rem_euclid(l as isize) as usize;
-        let m = |v, x, l| (v as isize - x as isize).rem_euclid(l as isize) as usize;
-
-        let next_face_id = match dir {
-            Dir::R => (1..=6)
-                .map(|x| face_lookup[c.0][p(c.1, x * cube_len, face_lookup[c.0].len())])
-                .find(|v| *v != 9)
-                .unwrap(),
-            Dir::L => (1..=6)
-                .map(|x| face_lookup[c.0][m(c.1, x * cube_len, face_lookup[c.0].len())])
-                .find(|v| *v != 9)
-                .unwrap(),
-            Dir::U => (1..=6)
-                .map(|x| face_lookup[m(c.0, x * cube_len, face_lookup.len())][c.1])
-                .find(|v| *v != 9)
-                .unwrap(),
-            Dir::D => (1..=6)
-                .map(|x| face_lookup[p(c.0, x * cube_len, face_lookup.len())][c.1])
-                .find(|v| *v != 9)
-                .unwrap(),
+    /// The face whose tile is `steps` faces away from `face` in direction
+    /// `dir` on the flat map, if there is one.
+    fn face_towards(&self, face: FaceId, dir: Dir, steps: usize) -> Option<FaceId> {
+        let c = self.faces[face as usize].top_left;
+        let offset = steps * self.cube_len;
+        let (r, c) = match dir {
+            Dir::R => (c.0, c.1 + offset),
+            Dir::D => (c.0 + offset, c.1),
+            Dir::L => (c.0, c.1.checked_sub(offset)?),
+            Dir::U => (c.0.checked_sub(offset)?, c.1),
         };
+        match self.face_lookup.get(r)?.get(c)? {
+            9 => None,
+            f => Some(FaceId::from(*f)),
+        }
+    }
 
-        (next_face_id.into(), dir)
-    };
-
-    solve(i2, &faces, &mut map, &face_lookup, cube_len, t)
-}
+    /// Walking off a face continues on the next face in the same direction on
+    /// the flat map, wrapping around at the edges. This is the map as a
+    /// torus.
+    pub fn flat_gluing(&self) -> Gluing {
+        self.wrapped_gluing(Wrap::Around, Wrap::Around)
+    }
 
-fn extract_faces(i1: &str, cube_len: usize) -> (Vec<Face>, Vec<Vec<char>>, Vec<Vec<usize>>) {
-    let max_cols = i1.lines().map(|l| l.len()).max().unwrap();
-    let max_rows = i1.lines().count();
+    /// Like [`Board::flat_gluing`], except that going off the top or bottom
+    /// of the map comes back mirrored left-to-right.
+    pub fn klein_bottle_gluing(&self) -> Gluing {
+        self.wrapped_gluing(Wrap::Around, Wrap::Mirrored)
+    }
 
-    let mut map = vec![vec![' '; max_cols]; max_rows];
-    for (r, l) in i1.lines().enumerate() {
-        for (c, x) in l.chars().enumerate() {
-            map[r][c] = x;
-        }
+    /// Going off the left or right of the map comes back mirrored
+    /// top-to-bottom, and the top and bottom of the map are boundaries.
+    pub fn mobius_strip_gluing(&self) -> Gluing {
+        self.wrapped_gluing(Wrap::Mirrored, Wrap::Boundary)
     }
 
-    // Find all six faces of the cube
-    let mut face_lookup = vec![vec![9usize; max_cols]; max_rows];
-    let mut face_id = 0;
+    fn wrapped_gluing(&self, horizontal: Wrap, vertical: Wrap) -> Gluing {
+        let rows = self.face_lookup.len().div_ceil(self.cube_len);
+        let cols = self.face_lookup[0].len().div_ceil(self.cube_len);
+        let face_at =
+            |r: usize, c: usize| match self.face_lookup[r * self.cube_len][c * self.cube_len] {
+                9 => None,
+                f => Some(FaceId::from(f)),
+            };
 
-    let mut faces = vec![];
-    let mut stk = vec![(0, map[0].iter().position(|c| *c == '.').unwrap())];
+        let mut table = [[None; 4]; 6];
+        for (face, row) in table.iter_mut().enumerate() {
+            let c = self.faces[face].top_left;
+            let (r, c) = (c.0 / self.cube_len, c.1 / self.cube_len);
+            for dir in [Dir::R, Dir::D, Dir::L, Dir::U] {
+                let (wrap, len, at) = match dir {
+                    Dir::R | Dir::L => (horizontal, cols, c),
+                    Dir::D | Dir::U => (vertical, rows, r),
+                };
+                // The tiles on the way to the edge of the map, and the tiles in
+                // the order they're reached coming back in from the other side.
+                let (ahead, entry): (Vec<usize>, Vec<usize>) = match dir {
+                    Dir::R | Dir::D => ((at + 1..len).collect(), (0..len).collect()),
+                    Dir::L | Dir::U => ((0..at).rev().collect(), (0..len).rev().collect()),
+                };
+                let tile = |x: usize, mirrored: bool| match dir {
+                    Dir::R | Dir::L if mirrored => face_at(rows - 1 - r, x),
+                    Dir::R | Dir::L => face_at(r, x),
+                    Dir::D | Dir::U if mirrored => face_at(x, cols - 1 - c),
+                    Dir::D | Dir::U => face_at(x, c),
+                };
+
+                let edge = |face, flipped| Edge { face, dir, flipped };
+                row[dir as usize] = match ahead.iter().find_map(|x| tile(*x, false)) {
+                    Some(f) => Some(edge(f, false)),
+                    None => match wrap {
+                        Wrap::Around => entry
+                            .iter()
+                            .find_map(|x| tile(*x, false))
+                            .map(|f| edge(f, false)),
+                        Wrap::Mirrored => entry
+                            .iter()
+                            .find_map(|x| tile(*x, true))
+                            .map(|f| edge(f, true)),
+                        Wrap::Boundary => None,
+                    },
+                };
+            }
+        }
+        Gluing { table }
+    }
 
-    while let Some(top_left) = stk.pop() {
-        if map[top_left.0][top_left.1] != ' ' {
-            let mut grid = vec![vec![false; cube_len]; cube_len];
+    /// Folds the net into a cube, working out where each face ends up in 3D,
+    /// and glues together the edges which meet.
+    pub fn cube_gluing(&self) -> Result<Gluing, NetError> {
+        use Dir::*;
 
-            for r in top_left.0..top_left.0 + cube_len {
-                for c in top_left.1..top_left.1 + cube_len {
-                    face_lookup[r][c] = face_id;
-                    grid[r - top_left.0][c - top_left.1] = map[r][c] == '#';
+        let mut frames: [Option<Frame>; 6] = [None; 6];
+        frames[0] = Some(Frame {
+            normal: (0, 0, 1),
+            right: (1, 0, 0),
+            down: (0, 1, 0),
+        });
+        let mut stk = vec![FaceId::A];
+        while let Some(face) = stk.pop() {
+            let frame = frames[face as usize].unwrap();
+            for dir in [R, D, L, U] {
+                if let Some(next) = self.face_towards(face, dir, 1) {
+                    if frames[next as usize].is_none() {
+                        frames[next as usize] = Some(frame.fold(dir));
+                        stk.push(next);
+                    }
                 }
             }
-            faces.push(Face { top_left, grid });
-            face_id += 1;
-            if top_left.0 >= cube_len && face_lookup[top_left.0 - cube_len][top_left.1] == 9 {
-                stk.push((top_left.0 - cube_len, top_left.1));
-            }
-            if top_left.0 + cube_len < map.len()
-                && face_lookup[top_left.0 + cube_len][top_left.1] == 9
-            {
-                stk.push((top_left.0 + cube_len, top_left.1));
-            }
-            if top_left.1 >= cube_len && face_lookup[top_left.0][top_left.1 - cube_len] == 9 {
-                stk.push((top_left.0, top_left.1 - cube_len));
+        }
+        let frames = frames.map(|f| f.unwrap());
+
+        for a in 0..6 {
+            for b in a + 1..6 {
+                if frames[a].normal == frames[b].normal {
+                    return Err(NetError::Overlap(a.into(), b.into()));
+                }
             }
-            if top_left.1 + cube_len < map[top_left.0].len()
-                && face_lookup[top_left.0][top_left.1 + cube_len] == 9
-            {
-                stk.push((top_left.0, top_left.1 + cube_len));
+        }
+
+        let mut table = [[None; 4]; 6];
+        for (face, row) in table.iter_mut().enumerate() {
+            let frame = frames[face];
+            for dir in [R, D, L, U] {
+                // Walking off the edge takes us onto the face on that side of
+                // the cube, heading away from the face we just left.
+                let towards = frame.towards(dir);
+                let next = frames.iter().position(|f| f.normal == towards).unwrap();
+                let back = [R, D, L, U]
+                    .into_iter()
+                    .find(|d| frames[next].towards(*d) == frame.normal)
+                    .unwrap();
+                row[dir as usize] = Some(Edge {
+                    face: next.into(),
+                    dir: back.reverse(),
+                    flipped: false,
+                });
             }
         }
+
+        Ok(Gluing { table })
     }
+}
 
-    assert_eq!(faces.len(), 6);
+type Vec3 = (i32, i32, i32);
 
-    (faces, map, face_lookup)
+fn neg(v: Vec3) -> Vec3 {
+    (-v.0, -v.1, -v.2)
 }
 
-fn compute_traversals(face_lookup: &[Vec<usize>]) -> [[(FaceId, Dir); 4]; 6] {
-    let mut tab = [[None; 4]; 6];
-    use Dir::*;
-    use FaceId::*;
-
-    for (r, row) in face_lookup.iter().enumerate().skip(1) {
-        for (c, f) in row.iter().copied().enumerate().skip(1) {
-            let p_c = face_lookup[r][c - 1];
-            let p_r = face_lookup[r - 1][c];
-            if p_c != f && p_c != 9 && f != 9 {
-                tab[p_c][R as usize] = Some((FaceId::from(f), R));
-                tab[f][L as usize] = Some((FaceId::from(p_c), L));
-            }
-            if p_r != f && p_r != 9 && f != 9 {
-                tab[p_r][D as usize] = Some((FaceId::from(f), D));
-                tab[f][U as usize] = Some((FaceId::from(p_r), U));
-            }
+/// Where a face sits once the net is folded: its outward normal, and the
+/// directions its columns and rows increase in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Frame {
+    normal: Vec3,
+    right: Vec3,
+    down: Vec3,
+}
+
+impl Frame {
+    fn towards(self, dir: Dir) -> Vec3 {
+        match dir {
+            Dir::R => self.right,
+            Dir::D => self.down,
+            Dir::L => neg(self.right),
+            Dir::U => neg(self.down),
         }
     }
 
-    while tab.iter().any(|l| l.iter().any(|v| v.is_none())) {
-        for a in [A, B, C, X, Y, Z] {
-            for dir in [R, D, L, U] {
-                if let Some((b, bd)) = tab[a as usize][dir as usize] {
-                    let dir2 = dir.turn_right();
-                    if let Some((c, cd)) = tab[a as usize][dir2 as usize] {
-                        let b_out = dir2.match_rot(dir, bd);
-                        let c_out = dir.match_rot(dir2, cd);
-                        tab[b as usize][b_out as usize] = Some((c, c_out.reverse()));
-                        tab[c as usize][c_out as usize] = Some((b, b_out.reverse()));
-                    }
-                }
-            }
+    /// The frame of the face adjacent in `dir` on the net, which folds over
+    /// the shared edge to face the way we were heading.
+    fn fold(self, dir: Dir) -> Frame {
+        let (normal, across) = (self.towards(dir), neg(self.normal));
+        match dir {
+            Dir::R => Frame {
+                normal,
+                right: across,
+                ..self
+            },
+            Dir::L => Frame {
+                normal,
+                right: neg(across),
+                ..self
+            },
+            Dir::D => Frame {
+                normal,
+                down: across,
+                ..self
+            },
+            Dir::U => Frame {
+                normal,
+                down: neg(across),
+                ..self
+            },
         }
     }
+}
+
+struct State<'a, 'b> {
+    face: FaceId,
+    faces: &'a [Face],
+    cube_len: isize,
+    pos: (usize, usize),
+    dir: Dir,
+    history: &'b mut Vec<Vec<char>>,
+}
+
+impl<'a, 'b> State<'a, 'b> {
+    fn orig_pos(&self) -> (usize, usize) {
+        let d = self.faces[self.face as usize].top_left;
+        (self.pos.0 + d.0, self.pos.1 + d.1)
+    }
+
+    fn blocked(&self, face: FaceId, pos: (usize, usize)) -> bool {
+        self.faces[face as usize].grid[pos.0][pos.1]
+    }
 
-    let mut tab2 = [[(A, D); 4]; 6];
+    fn answer(&self) -> usize {
+        let pos = self.orig_pos();
+        1000 * (pos.0 + 1) + 4 * (pos.1 + 1) + self.dir as usize
+    }
 
-    for (idx, l) in tab.into_iter().enumerate() {
-        for (idx2, d) in l.into_iter().enumerate() {
-            tab2[idx][idx2] = d.unwrap();
+    fn step(&self) -> Step {
+        Step {
+            face: self.face,
+            pos: self.pos,
+            dir: self.dir,
         }
     }
 
-    tab2
+    fn update(&mut self, new_face: FaceId, new_pos: (usize, usize), new_dir: Dir) {
+        self.face = new_face;
+        self.dir = new_dir;
+        self.pos = new_pos;
+
+        let o = self.orig_pos();
+        self.history[o.0][o.1] = new_dir.sym();
+    }
 }
 
-pub fn part_2(input: &str, cube_len: usize) -> usize {
-    let (i1, i2) = input.split_once("\n\n").unwrap();
-    let (faces, mut map, face_lookup) = extract_faces(i1, cube_len);
+/// Where you are after one instruction of the path, in the coordinates of the
+/// face you're on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub face: FaceId,
+    pub pos: (usize, usize),
+    pub dir: Dir,
+}
+
+/// The result of following the path across a board.
+pub struct Walk {
+    pub password: usize,
+    /// The position after every instruction, whether a move or a turn.
+    pub trace: Vec<Step>,
+    history: Vec<Vec<char>>,
+    face_lookup: Vec<Vec<usize>>,
+}
+
+impl Walk {
+    /// The map with the last facing on every visited tile drawn as an arrow.
+    pub fn render_path(&self) -> String {
+        render_grid(&self.history, |c| c)
+    }
+
+    /// The map with each tile labelled with the face it belongs to.
+    pub fn render_faces(&self) -> String {
+        render_grid(&self.face_lookup, |f| match f {
+            9 => ' ',
+            f => format!("{:?}", FaceId::from(f)).chars().next().unwrap(),
+        })
+    }
+}
+
+fn render_grid<T: Copy>(grid: &[Vec<T>], f: impl Fn(T) -> char) -> String {
+    let mut out = String::new();
+    for row in grid {
+        let line = row.iter().map(|x| f(*x)).collect::<String>();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+impl Board {
+    /// Follows `path` from the top-left open tile, using `topology` to decide
+    /// where walking off each face leads.
+    pub fn walk(&self, path: &str, topology: &impl Topology) -> Walk {
+        let mut history = self.map.clone();
+        let mut state = State {
+            faces: &self.faces,
+            face: FaceId::A,
+            pos: (0, 0),
+            dir: Dir::R,
+            history: &mut history,
+            cube_len: self.cube_len as isize,
+        };
+        state.update(state.face, state.pos, state.dir);
+
+        let mut trace = vec![];
+        let mut dist = None;
+        for c in path.trim().chars() {
+            if let Some(v) = c.to_digit(10) {
+                dist = Some(dist.unwrap_or(0) * 10 + v);
+                continue;
+            }
+
+            if let Some(d) = dist.take() {
+                mv(&mut state, topology, d);
+                trace.push(state.step());
+            }
+            let dir = match c {
+                'L' => state.dir.turn_left(),
+                'R' => state.dir.turn_right(),
+                _ => unreachable!("'{:?}'", c),
+            };
+            state.update(state.face, state.pos, dir);
+            trace.push(state.step());
+        }
+        if let Some(d) = dist {
+            mv(&mut state, topology, d);
+            trace.push(state.step());
+        }
+
+        Walk {
+            password: state.answer(),
+            trace,
+            history,
+            face_lookup: self.face_lookup.clone(),
+        }
+    }
+}
 
-    let t = compute_traversals(&face_lookup);
-    let t2 = |face_id, dir| t[face_id as usize][dir as usize];
+pub fn part_1(input: &str) -> usize {
+    let (i1, i2) = input.split_once("\n\n").unwrap();
+    let board = Board::parse(i1).unwrap();
+    board.walk(i2, &board.flat_gluing()).password
+}
 
-    solve(i2, &faces, &mut map, &face_lookup, cube_len, t2)
+pub fn part_2(input: &str) -> usize {
+    let (i1, i2) = input.split_once("\n\n").unwrap();
+    let board = Board::parse(i1).unwrap();
+    board.walk(i2, &board.cube_gluing().unwrap()).password
 }
 
-fn mv<'a, 'b>(state: &mut State<'a, 'b>, traverse: &impl Fn(FaceId, Dir) -> (FaceId, Dir), d: u32) {
+fn mv<'a, 'b>(state: &mut State<'a, 'b>, topology: &impl Topology, d: u32) {
     for _ in 0..d {
         let next_pos = state.dir.apply(state.pos);
         let (next_face, next_dir, next_pos) = if next_pos.0 < 0
@@ -541,7 +853,14 @@ fn mv<'a, 'b>(state: &mut State<'a, 'b>, traverse: &impl Fn(FaceId, Dir) -> (Fac
             || next_pos.1 < 0
             || next_pos.1 >= state.cube_len
         {
-            let (next_face, next_dir) = traverse(state.face, state.dir);
+            let Some(Edge {
+                face: next_face,
+                dir: next_dir,
+                flipped,
+            }) = topology.cross(state.face, state.dir)
+            else {
+                break;
+            };
             let x = (
                 next_pos.0.rem_euclid(state.cube_len),
                 next_pos.1.rem_euclid(state.cube_len),
@@ -549,23 +868,29 @@ fn mv<'a, 'b>(state: &mut State<'a, 'b>, traverse: &impl Fn(FaceId, Dir) -> (Fac
 
             use Dir::*;
 
-            let np = match (state.dir, next_dir) {
+            // Turning R the same way the heading turned tells us how the
+            // coordinate system rotated.
+            let np = match R.match_rot(state.dir, next_dir) {
                 // Not rotated, so we dont need to muck with the coordinate system
-                (R, R) | (D, D) | (L, L) | (U, U) => x,
+                R => x,
                 // Rotated clockwise
-                (R, D) | (D, L) | (L, U) | (U, R) => (x.1, state.cube_len - 1 - x.0),
-
+                D => (x.1, state.cube_len - 1 - x.0),
                 // Rotated counter-clockwise
-                (D, R) | (L, D) | (U, L) | (R, U) => (state.cube_len - 1 - x.1, x.0),
+                U => (state.cube_len - 1 - x.1, x.0),
                 // Flipped 180
-                (D, U) | (U, D) | (L, R) | (R, L) => {
-                    (state.cube_len - 1 - x.0, state.cube_len - 1 - x.1)
-                }
+                L => (state.cube_len - 1 - x.0, state.cube_len - 1 - x.1),
             };
-            let np = (
+            let mut np = (
                 np.0.rem_euclid(state.cube_len),
                 np.1.rem_euclid(state.cube_len),
             );
+            if flipped {
+                // Mirror the coordinate that runs along the edge we crossed.
+                match next_dir {
+                    R | L => np.0 = state.cube_len - 1 - np.0,
+                    D | U => np.1 = state.cube_len - 1 - np.1,
+                }
+            }
             (next_face, next_dir, np)
         } else {
             (state.face, state.dir, next_pos)
@@ -583,7 +908,9 @@ fn mv<'a, 'b>(state: &mut State<'a, 'b>, traverse: &impl Fn(FaceId, Dir) -> (Fac
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_22::{part_1, part_2};
+    use crate::day_22::{
+        mv, part_1, part_2, Board, Dir, Edge, FaceId, Gluing, NetError, State, Step,
+    };
 
     const INPUTS: &str = r#"        ...#
         .#..
@@ -601,21 +928,256 @@ pub mod tests {
 10R5L5R10L4R5L5"#;
     #[test]
     pub fn test_day_22_example_part1() {
-        assert_eq!(part_1(INPUTS, 4), 6032);
+        assert_eq!(part_1(INPUTS), 6032);
     }
 
     #[test]
     pub fn test_day_22_part1() {
-        assert_eq!(part_1(include_str!("input/day_22.txt"), 50), 36518);
+        assert_eq!(part_1(include_str!("input/day_22.txt")), 36518);
     }
 
     #[test]
     pub fn test_day_22_example_part2() {
-        assert_eq!(part_2(INPUTS, 4), 5031);
+        assert_eq!(part_2(INPUTS), 5031);
     }
 
     #[test]
     pub fn test_day_22_part2() {
-        assert_eq!(part_2(include_str!("input/day_22.txt"), 50), 143208);
+        assert_eq!(part_2(include_str!("input/day_22.txt")), 143208);
+    }
+
+    const NETS: [&str; 11] = [
+        "#...\n####\n#...",
+        "#...\n####\n.#..",
+        "#...\n####\n..#.",
+        "#...\n####\n...#",
+        ".#..\n####\n.#..",
+        ".#..\n####\n..#.",
+        "##..\n.###\n.#..",
+        "##..\n.###\n..#.",
+        "##..\n.###\n...#",
+        "##..\n.##.\n..##",
+        "###..\n..###",
+    ];
+
+    /// Draws a net given as a grid of face tiles, in any of its eight
+    /// rotations and reflections, with faces `cube_len` tiles across.
+    fn draw_net(net: &str, transform: usize, cube_len: usize) -> String {
+        let mut tiles = net
+            .lines()
+            .map(|l| l.chars().map(|c| c == '#').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for _ in 0..transform % 4 {
+            tiles = (0..tiles[0].len())
+                .map(|c| (0..tiles.len()).rev().map(|r| tiles[r][c]).collect())
+                .collect();
+        }
+        if transform >= 4 {
+            tiles.iter_mut().for_each(|row| row.reverse());
+        }
+
+        let mut out = String::new();
+        for row in &tiles {
+            for _ in 0..cube_len {
+                let line = row
+                    .iter()
+                    .map(|t| if *t { "." } else { " " }.repeat(cube_len))
+                    .collect::<String>();
+                out.push_str(line.trim_end());
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    #[test]
+    pub fn test_day_22_all_nets_fold() {
+        for net in NETS {
+            for transform in 0..8 {
+                let map = draw_net(net, transform, 3);
+                let board = Board::parse(&map).unwrap();
+                assert_eq!(board.cube_len(), 3);
+                let gluing = board.cube_gluing().unwrap();
+
+                // Walking straight around the cube from any edge always comes
+                // back to where it started after four faces.
+                for face in 0..6 {
+                    for dir in [Dir::R, Dir::D, Dir::L, Dir::U] {
+                        let edge = gluing.get(FaceId::from(face), dir).unwrap();
+                        assert_ne!(edge.face as usize, face);
+                        assert!(!edge.flipped);
+                        let back = gluing.get(edge.face, edge.dir.reverse()).unwrap();
+                        assert_eq!((back.face, back.dir), (FaceId::from(face), dir.reverse()));
+
+                        let mut history = board.map.clone();
+                        let mut state = State {
+                            face: FaceId::from(face),
+                            faces: &board.faces,
+                            cube_len: 3,
+                            pos: (1, 0),
+                            dir,
+                            history: &mut history,
+                        };
+                        mv(&mut state, &gluing, 12);
+                        assert_eq!(
+                            (state.face as usize, state.pos, state.dir),
+                            (face, (1, 0), dir),
+                            "{map}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_22_invalid_nets() {
+        let parse = |map: &str| Board::parse(map).err();
+        assert_eq!(parse(""), Some(NetError::Empty));
+        assert_eq!(parse("..\n.."), Some(NetError::Area(4)));
+        // Six faces, but folding the rectangle wraps around onto itself.
+        let board = Board::parse(&draw_net("###\n###", 0, 2)).unwrap();
+        assert!(matches!(board.cube_gluing(), Err(NetError::Overlap(_, _))));
+        assert_eq!(
+            parse(&draw_net("#.#.#.#.#.#", 0, 1)),
+            Some(NetError::FaceCount(1))
+        );
+        assert_eq!(
+            parse(" ............\n............"),
+            Some(NetError::Misaligned((0, 1)))
+        );
+
+        // A blank first line is fine as long as the faces still line up.
+        let board = Board::parse("\n#\n####\n#").unwrap();
+        assert_eq!(board.cube_len(), 1);
+        assert!(board.cube_gluing().is_ok());
+        let (map, _) = INPUTS.split_once("\n\n").unwrap();
+        assert_eq!(
+            parse(&format!("\n{map}")),
+            Some(NetError::Misaligned((1, 8)))
+        );
+    }
+
+    #[test]
+    pub fn test_day_22_example_gluing() {
+        let (map, _) = INPUTS.split_once("\n\n").unwrap();
+        let board = Board::parse(map).unwrap();
+        assert_eq!(board.cube_len(), 4);
+
+        let table = board.cube_gluing().unwrap().to_string();
+        assert_eq!(table.lines().count(), 24);
+        assert!(table.starts_with("A R -> "));
+    }
+
+    #[test]
+    pub fn test_day_22_example_render() {
+        let (map, path) = INPUTS.split_once("\n\n").unwrap();
+        let board = Board::parse(map).unwrap();
+        let walk = board.walk(path, &board.cube_gluing().unwrap());
+        assert_eq!(walk.password, 5031);
+
+        // 10R5L5R10L4R5L5 is 13 instructions.
+        assert_eq!(walk.trace.len(), 13);
+        assert_eq!(
+            walk.trace[0],
+            Step {
+                face: FaceId::A,
+                pos: (0, 2),
+                dir: Dir::R
+            }
+        );
+        assert_eq!(walk.trace[1].dir, Dir::D);
+
+        // The same drawing as the puzzle, with the arrows from `Dir::sym`.
+        assert_eq!(
+            walk.render_path(),
+            "        →→↓#
+        .#↓.
+        #.↓.
+        ..↓.
+...#..↑...↓#
+.→→→→→↑.#.→→
+.↑#....#....
+.↑........#.
+        ...#..↓.
+        .....#↓.
+        .#↓←←←←.
+        ..↓...#.
+"
+        );
+
+        assert_eq!(
+            walk.render_faces(),
+            "        AAAA
+        AAAA
+        AAAA
+        AAAA
+XXXXCCCCBBBB
+XXXXCCCCBBBB
+XXXXCCCCBBBB
+XXXXCCCCBBBB
+        YYYYZZZZ
+        YYYYZZZZ
+        YYYYZZZZ
+        YYYYZZZZ
+"
+        );
+    }
+
+    #[test]
+    pub fn test_day_22_other_surfaces() {
+        // A 2x3 rectangle of faces, 2 tiles across each: 4 rows by 6 columns.
+        let board = Board::parse(&"......\n".repeat(4)).unwrap();
+        let password = |path: &str, topology: &Gluing| board.walk(path, topology).password;
+
+        let torus = board.flat_gluing();
+        let klein = board.klein_bottle_gluing();
+        let mobius = board.mobius_strip_gluing();
+
+        // Going up off the top of the map comes back in at the bottom, either
+        // in the same column or the mirrored one.
+        assert_eq!(password("L1", &torus), 4000 + 4 + 3);
+        assert_eq!(password("L1", &klein), 4000 + 24 + 3);
+        assert_eq!(password("L4", &klein), 1000 + 24 + 3);
+        assert_eq!(password("L8", &klein), 1000 + 4 + 3);
+        // ...but the strip only has edges at the top and bottom.
+        assert_eq!(password("L5", &mobius), 1000 + 4 + 3);
+
+        // Going right off the map comes back in on the left, upside down on
+        // the strip.
+        assert_eq!(password("6", &torus), 1000 + 4);
+        assert_eq!(password("6", &klein), 1000 + 4);
+        assert_eq!(password("6", &mobius), 4000 + 4);
+        assert_eq!(password("12", &mobius), 1000 + 4);
+    }
+
+    #[test]
+    pub fn test_day_22_custom_gluing() {
+        let (map, path) = INPUTS.split_once("\n\n").unwrap();
+        let board = Board::parse(map).unwrap();
+        let cube = board.cube_gluing().unwrap();
+
+        // The dump reads back in as the same table.
+        let custom = Gluing::parse(&cube.to_string()).unwrap();
+        assert_eq!(custom, cube);
+        assert_eq!(board.walk(path, &custom).password, 5031);
+
+        let klein = board.klein_bottle_gluing();
+        assert_eq!(Gluing::parse(&klein.to_string()), Some(klein));
+        let mobius = board.mobius_strip_gluing();
+        assert_eq!(Gluing::parse(&mobius.to_string()), Some(mobius));
+
+        let table = Gluing::parse("A R -> B D flipped\nA U -> -").unwrap();
+        assert_eq!(
+            table.get(FaceId::A, Dir::R),
+            Some(Edge {
+                face: FaceId::B,
+                dir: Dir::D,
+                flipped: true
+            })
+        );
+        assert_eq!(table.get(FaceId::A, Dir::U), None);
+        assert_eq!(table.get(FaceId::Z, Dir::L), None);
+        assert_eq!(Gluing::parse("A R -> Q D"), None);
     }
 }
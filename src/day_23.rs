@@ -1,144 +1,499 @@
 // See day_23_prompt.txt
 use std::collections::{HashMap, HashSet};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
-#[repr(isize)]
-enum Dir {
-    N = 0,
-    NE = 1,
-    E = 2,
-    SE = 3,
-    S = 4,
-    SW = 5,
-    W = 6,
-    NW = 7,
-}
-
-use Dir::*;
-
-const ALL_DIRS: [Dir; 8] = [N, NE, E, SE, S, SW, W, NW];
-
-fn next_pos(pos: (isize, isize), dir: Dir) -> (isize, isize) {
-    let pos = (pos.0, pos.1);
-
-    let n_pos = match dir {
-        N => (pos.0 - 1, pos.1),
-        NE => (pos.0 - 1, pos.1 + 1),
-        E => (pos.0, pos.1 + 1),
-        SE => (pos.0 + 1, pos.1 + 1),
-        S => (pos.0 + 1, pos.1),
-        SW => (pos.0 + 1, pos.1 - 1),
-        W => (pos.0, pos.1 - 1),
-        NW => (pos.0 - 1, pos.1 - 1),
-    };
-
-    (n_pos.0, n_pos.1)
-}
-
-fn run_to_completion(
-    elf_positions: &mut HashSet<(isize, isize)>,
-    max_steps: Option<usize>,
-) -> usize {
-    let seq = vec![
-        ([N, NE, NW], N),
-        ([S, SE, SW], S),
-        ([W, NW, SW], W),
-        ([E, NE, SE], E),
-    ];
-
-    let range = match max_steps {
-        Some(v) => Box::new(0..v),
-        None => Box::new(0usize..) as Box<dyn Iterator<Item = usize>>,
-    };
-
-    let mut new_elf_positions: HashMap<_, Vec<_>> = HashMap::new();
-    for r in range {
-        for elf in elf_positions.iter() {
-            let mut neighbors = [false; 8];
-
-            for (idx, dir) in ALL_DIRS.iter().enumerate() {
-                neighbors[idx] = elf_positions.contains(&next_pos(*elf, *dir));
-            }
-
-            if neighbors.iter().all(|f| !f) {
-                // continue
+fn parse(input: &str) -> HashSet<(isize, isize)> {
+    let mut elf_positions = HashSet::new();
+    for (r, line) in input.lines().filter(|l| !l.is_empty()).enumerate() {
+        for (c, v) in line.chars().enumerate() {
+            if v == '#' {
+                elf_positions.insert((r as isize, c as isize));
+            }
+        }
+    }
+    elf_positions
+}
+
+type Row = Vec<u64>;
+
+/// Each bit `c` of the result is bit `c - 1` of `row`: whether there's an elf
+/// directly to the west.
+fn west_of(row: &[u64]) -> Row {
+    let mut out = vec![0; row.len()];
+    for i in 0..row.len() {
+        out[i] = row[i] << 1 | if i > 0 { row[i - 1] >> 63 } else { 0 };
+    }
+    out
+}
+
+/// Each bit `c` of the result is bit `c + 1` of `row`: whether there's an elf
+/// directly to the east.
+fn east_of(row: &[u64]) -> Row {
+    let mut out = vec![0; row.len()];
+    for i in 0..row.len() {
+        out[i] = row[i] >> 1
+            | if i + 1 < row.len() {
+                row[i + 1] << 63
             } else {
-                for i in 0..seq.len() {
-                    let (search, dir) = seq[(r + i) % seq.len()];
-                    if search.iter().all(|x| !neighbors[*x as usize]) {
-                        new_elf_positions
-                            .entry(next_pos(*elf, dir))
-                            .or_default()
-                            .push(*elf);
-                        break;
-                    }
-                }
+                0
             };
+    }
+    out
+}
+
+fn zip_with(a: &[u64], b: &[u64], f: impl Fn(u64, u64) -> u64) -> Row {
+    a.iter().zip(b).map(|(x, y)| f(*x, *y)).collect()
+}
+
+/// The grove as a dense grid of bits, one row of 64-bit words per row of
+/// ground, so that a whole row of elves can be checked at once. The grid grows
+/// as the elves spread out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitboard {
+    rows: Vec<Row>,
+    words: usize,
+    /// The position of bit 0 of the first row.
+    origin: (isize, isize),
+    round: usize,
+}
+
+impl Bitboard {
+    pub fn parse(input: &str) -> Self {
+        let elves = parse(input);
+        let cols = elves.iter().map(|e| e.1 + 1).max().unwrap_or(0) as usize;
+        let words = cols.div_ceil(64).max(1);
+        let height = elves.iter().map(|e| e.0 + 1).max().unwrap_or(0) as usize;
+
+        let mut rows = vec![vec![0; words]; height];
+        for (r, c) in elves {
+            rows[r as usize][c as usize / 64] |= 1 << (c % 64);
         }
 
-        let mut updated = false;
-        for (new, old) in new_elf_positions.drain() {
-            if old.len() == 1 {
-                updated = true;
-                elf_positions.remove(&old[0]);
-                elf_positions.insert(new);
+        Bitboard {
+            rows,
+            words,
+            origin: (0, 0),
+            round: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+            .iter()
+            .flatten()
+            .map(|w| w.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every elf's position, in row-major order.
+    pub fn positions(&self) -> Vec<(isize, isize)> {
+        let mut positions = vec![];
+        for (r, row) in self.rows.iter().enumerate() {
+            for (i, word) in row.iter().enumerate() {
+                let mut w = *word;
+                while w != 0 {
+                    let c = i * 64 + w.trailing_zeros() as usize;
+                    positions.push((self.origin.0 + r as isize, self.origin.1 + c as isize));
+                    w &= w - 1;
+                }
             }
         }
+        positions
+    }
+
+    /// Empty tiles in the smallest rectangle containing every elf.
+    pub fn empty_ground(&self) -> usize {
+        let positions = self.positions();
+        let (Some(min_r), Some(max_r)) = (
+            positions.iter().map(|e| e.0).min(),
+            positions.iter().map(|e| e.0).max(),
+        ) else {
+            return 0;
+        };
+        let min_c = positions.iter().map(|e| e.1).min().unwrap();
+        let max_c = positions.iter().map(|e| e.1).max().unwrap();
 
-        if !updated {
-            return r;
+        ((max_r - min_r + 1) * (max_c - min_c + 1)) as usize - positions.len()
+    }
+
+    /// Makes sure there's an empty row and column all the way around the
+    /// elves, so nobody can step off the grid.
+    fn ensure_margin(&mut self) {
+        if self.rows.first().is_none_or(|r| r.iter().any(|w| *w != 0)) {
+            self.rows.insert(0, vec![0; self.words]);
+            self.origin.0 -= 1;
+        }
+        if self.rows.last().is_none_or(|r| r.iter().any(|w| *w != 0)) {
+            self.rows.push(vec![0; self.words]);
+        }
+        if self.rows.iter().any(|r| r[0] & 1 != 0) {
+            self.rows.iter_mut().for_each(|r| r.insert(0, 0));
+            self.words += 1;
+            self.origin.1 -= 64;
+        }
+        if self.rows.iter().any(|r| r[self.words - 1] >> 63 != 0) {
+            self.rows.iter_mut().for_each(|r| r.push(0));
+            self.words += 1;
         }
     }
-    max_steps.unwrap()
-}
 
-pub fn part_1(input: &str) -> usize {
-    let grid = input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| l.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let mut elf_positions = HashSet::new();
+    /// Runs a single round, returning how many elves moved.
+    pub fn step(&mut self) -> usize {
+        self.ensure_margin();
+        let height = self.rows.len();
+        let zero = vec![0; self.words];
+        let row = |r: isize| -> &[u64] {
+            if r < 0 || r as usize >= height {
+                &zero
+            } else {
+                &self.rows[r as usize]
+            }
+        };
 
-    for (r, line) in grid.iter().enumerate() {
-        for (c, v) in line.iter().enumerate() {
-            if *v == '#' {
-                elf_positions.insert((r as isize, c as isize));
+        // Which elves propose moving north, south, west and east, per row.
+        let mut proposals = [vec![], vec![], vec![], vec![]];
+        for r in 0..height as isize {
+            let (n, cur, s) = (row(r - 1), row(r), row(r + 1));
+            let (nw, ne) = (west_of(n), east_of(n));
+            let (sw, se) = (west_of(s), east_of(s));
+            let (w, e) = (west_of(cur), east_of(cur));
+
+            let blocked = [
+                zip_with(&zip_with(n, &nw, |a, b| a | b), &ne, |a, b| a | b),
+                zip_with(&zip_with(s, &sw, |a, b| a | b), &se, |a, b| a | b),
+                zip_with(&zip_with(&w, &nw, |a, b| a | b), &sw, |a, b| a | b),
+                zip_with(&zip_with(&e, &ne, |a, b| a | b), &se, |a, b| a | b),
+            ];
+
+            // Elves with nobody around them don't do anything.
+            let mut undecided = cur.to_vec();
+            for (i, word) in undecided.iter_mut().enumerate() {
+                *word &= blocked[0][i] | blocked[1][i] | blocked[2][i] | blocked[3][i];
+            }
+
+            let mut row_proposals = [zero.clone(), zero.clone(), zero.clone(), zero.clone()];
+            for i in 0..4 {
+                let d = (self.round + i) % 4;
+                row_proposals[d] = zip_with(&undecided, &blocked[d], |a, b| a & !b);
+                undecided = zip_with(&undecided, &row_proposals[d], |a, b| a & !b);
+            }
+            for (d, p) in row_proposals.into_iter().enumerate() {
+                proposals[d].push(p);
             }
         }
+        let [north, south, west, east] = proposals;
+        let prop = |p: &Vec<Row>, r: isize| -> Row {
+            if r < 0 || r as usize >= height {
+                zero.clone()
+            } else {
+                p[r as usize].clone()
+            }
+        };
+
+        // Two elves can only ever propose the same tile from opposite sides,
+        // since anyone approaching from the side would have seen them.
+        let contested_v = (0..height as isize)
+            .map(|r| zip_with(&prop(&south, r - 1), &prop(&north, r + 1), |a, b| a & b))
+            .collect::<Vec<_>>();
+        let contested_h = (0..height)
+            .map(|r| zip_with(&east_of(&west[r]), &west_of(&east[r]), |a, b| a & b))
+            .collect::<Vec<_>>();
+
+        let mut moved = 0;
+        let mut next = Vec::with_capacity(height);
+        for r in 0..height as isize {
+            let ru = r as usize;
+            let leave_n = zip_with(&north[ru], &prop(&contested_v, r - 1), |a, b| a & !b);
+            let leave_s = zip_with(&south[ru], &prop(&contested_v, r + 1), |a, b| a & !b);
+            let leave_w = zip_with(&west[ru], &west_of(&contested_h[ru]), |a, b| a & !b);
+            let leave_e = zip_with(&east[ru], &east_of(&contested_h[ru]), |a, b| a & !b);
+
+            let arrive_v = zip_with(&prop(&north, r + 1), &prop(&south, r - 1), |a, b| a | b);
+            let arrive_h = zip_with(&east_of(&west[ru]), &west_of(&east[ru]), |a, b| a | b);
+
+            let mut new_row = self.rows[ru].clone();
+            for i in 0..self.words {
+                let leaving = leave_n[i] | leave_s[i] | leave_w[i] | leave_e[i];
+                moved += leaving.count_ones() as usize;
+                new_row[i] &= !leaving;
+                new_row[i] |= arrive_v[i] & !contested_v[ru][i];
+                new_row[i] |= arrive_h[i] & !contested_h[ru][i];
+            }
+            next.push(new_row);
+        }
+
+        self.rows = next;
+        self.round += 1;
+        moved
     }
 
-    let _ = run_to_completion(&mut elf_positions, Some(10));
+    /// Runs rounds until nobody moves, or `max_rounds` have happened,
+    /// returning how many elves moved in each round.
+    pub fn run(&mut self, max_rounds: Option<usize>) -> Vec<usize> {
+        let mut moved = vec![];
+        while max_rounds.is_none_or(|m| moved.len() < m) {
+            moved.push(self.step());
+            if moved.last() == Some(&0) {
+                break;
+            }
+        }
+        moved
+    }
+}
 
-    let min_r = elf_positions.iter().map(|e| e.0).min().unwrap();
-    let max_r = elf_positions.iter().map(|e| e.0).max().unwrap();
-    let min_c = elf_positions.iter().map(|e| e.1).min().unwrap();
-    let max_c = elf_positions.iter().map(|e| e.1).max().unwrap();
+/// One of the moves an elf may propose: it goes `step` if every offset in
+/// `check` is empty. If `check` doesn't include `step`, the move only happens
+/// if the tile is empty or its elf moves away in the same round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proposal<const D: usize> {
+    pub check: Vec<[isize; D]>,
+    pub step: [isize; D],
+}
 
-    ((max_r - min_r + 1) * (max_c - min_c + 1)) as usize - elf_positions.len()
+/// What to do when more than one elf proposes the same tile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Nobody moves, as in the puzzle.
+    CancelAll,
+    /// The elf which comes first in reading order moves, the rest stay.
+    FirstCome,
+    /// A randomly picked elf moves, the rest stay.
+    Random { seed: u64 },
 }
 
-pub fn part_2(input: &str) -> usize {
-    let grid = input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| l.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let mut elf_positions = HashSet::new();
+/// The rules of the diffusion, for elves living on a `D`-dimensional grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules<const D: usize> {
+    /// Elves with nobody at any of these offsets don't move.
+    pub neighborhood: Vec<[isize; D]>,
+    /// Tried in order, starting from a different one each round.
+    pub proposals: Vec<Proposal<D>>,
+    /// How far along the list of proposals the first one moves each round.
+    pub rotate_by: usize,
+    pub conflict: Conflict,
+}
 
-    for (r, line) in grid.iter().enumerate() {
-        for (c, v) in line.iter().enumerate() {
-            if *v == '#' {
-                elf_positions.insert((r as isize, c as isize));
+impl Rules<2> {
+    /// The puzzle's rules, with positions as `[row, col]`.
+    pub fn standard() -> Self {
+        let (n, s, w, e) = ([-1, 0], [1, 0], [0, -1], [0, 1]);
+        let (nw, ne, sw, se) = ([-1, -1], [-1, 1], [1, -1], [1, 1]);
+        Rules {
+            neighborhood: vec![n, ne, e, se, s, sw, w, nw],
+            proposals: vec![
+                Proposal {
+                    check: vec![n, ne, nw],
+                    step: n,
+                },
+                Proposal {
+                    check: vec![s, se, sw],
+                    step: s,
+                },
+                Proposal {
+                    check: vec![w, nw, sw],
+                    step: w,
+                },
+                Proposal {
+                    check: vec![e, ne, se],
+                    step: e,
+                },
+            ],
+            rotate_by: 1,
+            conflict: Conflict::CancelAll,
+        }
+    }
+
+    /// A hexagonal grid in axial `[q, r]` coordinates, where an elf moves
+    /// towards a side of its hexagon if that side and the two next to it are
+    /// free.
+    pub fn hexagonal() -> Self {
+        let sides: [[isize; 2]; 6] = [[0, -1], [1, -1], [1, 0], [0, 1], [-1, 1], [-1, 0]];
+        Rules {
+            neighborhood: sides.to_vec(),
+            proposals: (0..6)
+                .map(|i| Proposal {
+                    check: vec![sides[(i + 5) % 6], sides[i], sides[(i + 1) % 6]],
+                    step: sides[i],
+                })
+                .collect(),
+            rotate_by: 1,
+            conflict: Conflict::CancelAll,
+        }
+    }
+}
+
+impl Rules<3> {
+    /// Elves in 3D, moving towards one of the six faces of their cube if the
+    /// nine cubes on that side are free.
+    pub fn cubic() -> Self {
+        let mut neighborhood = vec![];
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if [x, y, z] != [0, 0, 0] {
+                        neighborhood.push([x, y, z]);
+                    }
+                }
             }
         }
+
+        let mut proposals = vec![];
+        for axis in 0..3 {
+            for sign in [-1, 1] {
+                let mut step = [0; 3];
+                step[axis] = sign;
+                proposals.push(Proposal {
+                    check: neighborhood
+                        .iter()
+                        .copied()
+                        .filter(|n| n[axis] == sign)
+                        .collect(),
+                    step,
+                });
+            }
+        }
+
+        Rules {
+            neighborhood,
+            proposals,
+            rotate_by: 1,
+            conflict: Conflict::CancelAll,
+        }
+    }
+}
+
+fn offset<const D: usize>(pos: [isize; D], by: [isize; D]) -> [isize; D] {
+    let mut out = pos;
+    for (o, b) in out.iter_mut().zip(by) {
+        *o += b;
+    }
+    out
+}
+
+/// Runs the diffusion for any set of [`Rules`]. Slower than the
+/// [`Bitboard`], which only knows the puzzle's rules.
+pub struct Engine<const D: usize> {
+    elves: HashSet<[isize; D]>,
+    rules: Rules<D>,
+    round: usize,
+    rng: u64,
+}
+
+impl<const D: usize> Engine<D> {
+    pub fn new(elves: impl IntoIterator<Item = [isize; D]>, rules: Rules<D>) -> Self {
+        let rng = match rules.conflict {
+            // xorshift gets stuck at zero.
+            Conflict::Random { seed } => seed.max(1),
+            _ => 1,
+        };
+        Engine {
+            elves: elves.into_iter().collect(),
+            rules,
+            round: 0,
+            rng,
+        }
+    }
+
+    /// Every elf's position, sorted.
+    pub fn positions(&self) -> Vec<[isize; D]> {
+        let mut positions = self.elves.iter().copied().collect::<Vec<_>>();
+        positions.sort();
+        positions
+    }
+
+    fn random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    /// Runs a single round, returning how many elves moved.
+    pub fn step(&mut self) -> usize {
+        let rules = &self.rules;
+        let occupied = |p| self.elves.contains(&p);
+
+        // Proposals in reading order of the elves making them, so that
+        // first-come is well-defined.
+        let mut proposed: HashMap<[isize; D], Vec<[isize; D]>> = HashMap::new();
+        for elf in self.positions() {
+            if !rules.neighborhood.iter().any(|n| occupied(offset(elf, *n))) {
+                continue;
+            }
+            let count = rules.proposals.len();
+            for i in 0..count {
+                let p = &rules.proposals[(self.round * rules.rotate_by + i) % count];
+                if p.check.iter().all(|c| !occupied(offset(elf, *c))) {
+                    proposed.entry(offset(elf, p.step)).or_default().push(elf);
+                    break;
+                }
+            }
+        }
+
+        let mut targets = proposed.into_iter().collect::<Vec<_>>();
+        targets.sort();
+
+        let mut moves = vec![];
+        for (target, elves) in targets {
+            let mover = match (elves.len(), self.rules.conflict) {
+                (1, _) => elves[0],
+                (_, Conflict::CancelAll) => continue,
+                (_, Conflict::FirstCome) => elves[0],
+                (n, Conflict::Random { .. }) => elves[(self.random() % n as u64) as usize],
+            };
+            moves.push((mover, target));
+        }
+
+        // A proposal whose `check` doesn't cover its `step` can aim at an
+        // elf. That's fine if the elf is moving out this round, but not if
+        // it stays, and cancelling one move can strand the elf behind it.
+        loop {
+            let leaving = moves.iter().map(|(from, _)| *from).collect::<HashSet<_>>();
+            let before = moves.len();
+            moves.retain(|(_, to)| !self.elves.contains(to) || leaving.contains(to));
+            if moves.len() == before {
+                break;
+            }
+        }
+        for (from, _) in &moves {
+            self.elves.remove(from);
+        }
+        self.elves.extend(moves.iter().map(|(_, to)| *to));
+
+        self.round += 1;
+        moves.len()
+    }
+
+    /// Runs rounds until nobody moves, or `max_rounds` have happened,
+    /// returning how many elves moved in each round.
+    pub fn run(&mut self, max_rounds: Option<usize>) -> Vec<usize> {
+        let mut moved = vec![];
+        while max_rounds.is_none_or(|m| moved.len() < m) {
+            moved.push(self.step());
+            if moved.last() == Some(&0) {
+                break;
+            }
+        }
+        moved
     }
-    run_to_completion(&mut elf_positions, None) + 1
+}
+
+pub fn part_1(input: &str) -> usize {
+    let mut board = Bitboard::parse(input);
+    board.run(Some(10));
+    board.empty_ground()
+}
+
+pub fn part_2(input: &str) -> usize {
+    Bitboard::parse(input).run(None).len()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_23::{part_1, part_2};
+    use crate::day_23::{parse, part_1, part_2, Bitboard, Conflict, Engine, Proposal, Rules};
     const INPUTS: &str = r#"..............
 ..............
 .......#......
@@ -172,4 +527,142 @@ pub mod tests {
     pub fn test_day_23_part2() {
         assert_eq!(part_2(include_str!("input/day_23.txt")), 988);
     }
+
+    #[test]
+    pub fn test_day_23_example_moves_per_round() {
+        let small = ".....\n..##.\n..#..\n.....\n..##.\n.....";
+        assert_eq!(Bitboard::parse(small).run(None), vec![3, 5, 3, 0]);
+
+        let moved = Bitboard::parse(INPUTS).run(None);
+        assert_eq!(moved.len(), 20);
+        assert_eq!(moved.last(), Some(&0));
+        assert!(moved[..19].iter().all(|m| *m > 0));
+    }
+
+    fn engine(input: &str, rules: Rules<2>) -> Engine<2> {
+        Engine::new(parse(input).into_iter().map(|(r, c)| [r, c]), rules)
+    }
+
+    #[test]
+    pub fn test_day_23_bitboard_matches_engine() {
+        let input = include_str!("input/day_23.txt");
+        let mut board = Bitboard::parse(input);
+        let mut elves = engine(input, Rules::standard());
+
+        for rounds in [1, 5, 30, 80] {
+            board.run(Some(rounds - board.round));
+            elves.run(Some(rounds - elves.round));
+            let expected = elves
+                .positions()
+                .into_iter()
+                .map(|[r, c]| (r, c))
+                .collect::<Vec<_>>();
+            assert_eq!(board.positions(), expected);
+        }
+    }
+
+    #[test]
+    pub fn test_day_23_conflict_policies() {
+        let small = ".....\n..##.\n..#..\n.....\n..##.\n.....";
+        assert_eq!(engine(small, Rules::standard()).run(None), vec![3, 5, 3, 0]);
+
+        // The elves at (2, 2) and (4, 2) both want (3, 2) in the first round.
+        let mut first = engine(
+            small,
+            Rules {
+                conflict: Conflict::FirstCome,
+                ..Rules::standard()
+            },
+        );
+        assert_eq!(first.step(), 4);
+        assert!(first.positions().contains(&[3, 2]));
+        assert!(!first.positions().contains(&[2, 2]));
+        assert!(first.positions().contains(&[4, 2]));
+
+        let random = |seed| {
+            let mut e = engine(
+                small,
+                Rules {
+                    conflict: Conflict::Random { seed },
+                    ..Rules::standard()
+                },
+            );
+            assert_eq!(e.step(), 4);
+            e.positions()
+        };
+        assert_eq!(random(7), random(7));
+        let winners = (0..32)
+            .map(|seed| random(seed).contains(&[2, 2]))
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(winners.len(), 2);
+    }
+
+    #[test]
+    pub fn test_day_23_fixed_schedule() {
+        // Without rotation both elves keep trying north first and never part.
+        let mut e = engine(
+            "##",
+            Rules {
+                rotate_by: 0,
+                ..Rules::standard()
+            },
+        );
+        assert_eq!(e.run(Some(5)), vec![2; 5]);
+        assert_eq!(e.positions(), vec![[-5, 0], [-5, 1]]);
+
+        let mut e = engine("##", Rules::standard());
+        assert_eq!(e.run(None), vec![2, 2, 2, 0]);
+        assert_eq!(e.positions(), vec![[0, -1], [0, 2]]);
+    }
+
+    #[test]
+    pub fn test_day_23_other_grids() {
+        let mut hex = Engine::new(
+            [[0, 0], [1, 0], [0, 1], [-1, 1], [-1, 0], [0, -1], [1, -1]],
+            Rules::hexagonal(),
+        );
+        assert_eq!(hex.run(None), vec![6, 0]);
+        assert_eq!(
+            hex.positions(),
+            vec![[-2, 0], [-2, 2], [0, -2], [0, 0], [0, 2], [2, -2], [2, 0]]
+        );
+
+        let mut cube = vec![];
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    cube.push([x, y, z]);
+                }
+            }
+        }
+        let mut cubic = Engine::new(cube, Rules::cubic());
+        let moved = cubic.run(None);
+        assert_eq!(moved.len(), 19);
+        assert_eq!(cubic.positions().len(), 27);
+    }
+
+    #[test]
+    pub fn test_day_23_moves_onto_elves() {
+        // Elves with a neighbour to the north or south step east without
+        // looking, so some of them aim at other elves.
+        let (n, s, e) = ([-1, 0], [1, 0], [0, 1]);
+        let rules = Rules {
+            neighborhood: vec![n, s],
+            proposals: vec![Proposal {
+                check: vec![],
+                step: e,
+            }],
+            rotate_by: 0,
+            conflict: Conflict::CancelAll,
+        };
+
+        // (0, 1) stays put, blocking (0, 0), which then blocks (0, -1).
+        // (1, -1) can follow (1, 0) as it leaves.
+        let mut engine = Engine::new([[0, -1], [0, 0], [0, 1], [1, -1], [1, 0]], rules);
+        assert_eq!(engine.step(), 2);
+        assert_eq!(
+            engine.positions(),
+            vec![[0, -1], [0, 0], [0, 1], [1, 0], [1, 1]]
+        );
+    }
 }
@@ -1,6 +1,7 @@
 // See day_24_prompt.txt
 
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct P {
@@ -58,115 +59,372 @@ const U: P = P { row: -1, col: 0 };
 const D: P = P { row: 1, col: 0 };
 const NOOP: P = P { row: 0, col: 0 };
 
-fn parse(input: &str) -> (HashSet<P>, HashSet<(P, P)>, P, P, P) {
-    let mut walls = HashSet::new();
-    let mut blizzards = HashSet::new();
+/// The map of the valley, with blizzards as they are at minute 0.
+pub struct Valley {
+    walls: HashSet<P>,
+    blizzards: HashSet<(P, P)>,
+    max: P,
+    start: P,
+    end: P,
+}
 
-    let mut start = None;
-    let mut end = None;
+impl Valley {
+    pub fn parse(input: &str) -> Self {
+        let mut walls = HashSet::new();
+        let mut blizzards = HashSet::new();
 
-    for (r, line) in input.lines().enumerate() {
-        for (c, ch) in line.chars().enumerate() {
-            let p = P::from((r as isize - 1, c as isize - 1));
-            match ch {
-                '#' => {
-                    walls.insert(p);
-                }
-                '>' => {
-                    blizzards.insert((p, R));
-                }
-                '<' => {
-                    blizzards.insert((p, L));
-                }
-                '^' => {
-                    blizzards.insert((p, U));
-                }
-                'v' => {
-                    blizzards.insert((p, D));
-                }
-                '.' if r == 0 && start.is_none() => {
-                    start = Some(p);
-                }
-                '.' => {
-                    end = Some(p);
-                }
-                _ => continue,
+        let mut start = None;
+        let mut end = None;
+
+        for (r, line) in input.lines().enumerate() {
+            for (c, ch) in line.chars().enumerate() {
+                let p = P::from((r as isize - 1, c as isize - 1));
+                match ch {
+                    '#' => {
+                        walls.insert(p);
+                    }
+                    '>' => {
+                        blizzards.insert((p, R));
+                    }
+                    '<' => {
+                        blizzards.insert((p, L));
+                    }
+                    '^' => {
+                        blizzards.insert((p, U));
+                    }
+                    'v' => {
+                        blizzards.insert((p, D));
+                    }
+                    '.' if r == 0 && start.is_none() => {
+                        start = Some(p);
+                    }
+                    '.' => {
+                        end = Some(p);
+                    }
+                    _ => continue,
+                };
+            }
+        }
+
+        let min_c = walls.iter().map(|p| p.col).min().unwrap();
+        let max_c = walls.iter().map(|p| p.col).max().unwrap();
+        let min_r = walls.iter().map(|p| p.row).min().unwrap();
+        let max_r = walls.iter().map(|p| p.row).max().unwrap();
+
+        // Fill in a wall above the start so we never need to check bounds.
+        for c in min_c..=max_c {
+            walls.insert((min_r - 1, c).into());
+        }
+
+        Valley {
+            walls,
+            blizzards,
+            max: P {
+                row: max_r,
+                col: max_c,
+            },
+            start: start.unwrap(),
+            end: end.unwrap(),
+        }
+    }
+
+    fn to_input(p: P) -> (usize, usize) {
+        ((p.row + 1) as usize, (p.col + 1) as usize)
+    }
+
+    fn from_input(pos: (usize, usize)) -> P {
+        P::from((pos.0 as isize - 1, pos.1 as isize - 1))
+    }
+
+    /// Position of the gap in the top wall, as `(row, col)` in the input.
+    pub fn entrance(&self) -> (usize, usize) {
+        Self::to_input(self.start)
+    }
+
+    /// Position of the gap in the bottom wall, as `(row, col)` in the input.
+    pub fn exit(&self) -> (usize, usize) {
+        Self::to_input(self.end)
+    }
+
+    /// Where every blizzard is after `minute` minutes.
+    pub fn state_at(&self, minute: usize) -> Snapshot {
+        let rows = (self.max.row + 2) as usize;
+        let cols = (self.max.col + 2) as usize;
+        let mut counts = vec![vec![0; cols]; rows];
+        let mut last = vec![vec![NOOP; cols]; rows];
+        for (p, d) in &self.blizzards {
+            let q = (*p + P::from((minute, minute)) * *d).wrap(self.max);
+            let (r, c) = Self::to_input(q);
+            counts[r][c] += 1;
+            last[r][c] = *d;
+        }
+
+        let cells = (0..rows)
+            .map(|r| {
+                (0..cols)
+                    .map(|c| {
+                        if self.walls.contains(&Self::from_input((r, c))) {
+                            return '#';
+                        }
+                        match (counts[r][c], last[r][c]) {
+                            (0, _) => '.',
+                            (1, R) => '>',
+                            (1, L) => '<',
+                            (1, U) => '^',
+                            (1, _) => 'v',
+                            (n, _) => char::from_digit(n.min(9), 10).unwrap(),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Snapshot { cells }
+    }
+}
+
+/// The valley at one minute, drawn the way the puzzle does: walls as `#`,
+/// lone blizzards as arrows and overlapping ones as their count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    cells: Vec<Vec<char>>,
+}
+
+impl Snapshot {
+    /// Marks the expedition's position with an `E`.
+    pub fn with_expedition(mut self, pos: (usize, usize)) -> Self {
+        self.cells[pos.0][pos.1] = 'E';
+        self
+    }
+
+    pub fn get(&self, pos: (usize, usize)) -> Option<char> {
+        self.cells.get(pos.0)?.get(pos.1).copied()
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, row) in self.cells.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", row.iter().collect::<String>())?;
+        }
+        Ok(())
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Which interior cells are covered by a blizzard at any minute.
+///
+/// Horizontal blizzards repeat every `width` minutes and vertical ones every
+/// `height` minutes, so each is precomputed over its own cycle and the whole
+/// valley repeats every `lcm(width, height)` minutes.
+struct Forecast {
+    width: usize,
+    height: usize,
+    horizontal: Vec<Vec<bool>>,
+    vertical: Vec<Vec<bool>>,
+}
+
+impl Forecast {
+    fn new(blizzards: &HashSet<(P, P)>, max: P) -> Self {
+        let (height, width) = (max.row as usize, max.col as usize);
+        let mut horizontal = vec![vec![false; width * height]; width];
+        let mut vertical = vec![vec![false; width * height]; height];
+
+        for (p, d) in blizzards {
+            let (occupancy, period) = if d.row == 0 {
+                (&mut horizontal, width)
+            } else {
+                (&mut vertical, height)
             };
+            for (t, cells) in occupancy.iter_mut().enumerate().take(period) {
+                let q = (*p + P::from((t, t)) * *d).wrap(max);
+                cells[q.row as usize * width + q.col as usize] = true;
+            }
+        }
+
+        Forecast {
+            width,
+            height,
+            horizontal,
+            vertical,
         }
     }
 
-    let min_c = walls.iter().map(|p| p.col).min().unwrap();
-    let max_c = walls.iter().map(|p| p.col).max().unwrap();
-    let min_r = walls.iter().map(|p| p.row).min().unwrap();
-    let max_r = walls.iter().map(|p| p.row).max().unwrap();
+    fn period(&self) -> usize {
+        self.width / gcd(self.width, self.height) * self.height
+    }
 
-    // Fill in a wall above the start so we never need to check bounds.
-    for c in min_c..=max_c {
-        walls.insert((min_r - 1, c).into());
+    /// Whether `p` is free of blizzards at minute `t`. Cells outside the
+    /// interior, like the entrance and exit, never have blizzards.
+    fn is_clear(&self, p: P, t: usize) -> bool {
+        if p.row < 0 || p.col < 0 || p.row as usize >= self.height || p.col as usize >= self.width {
+            return true;
+        }
+        let idx = p.row as usize * self.width + p.col as usize;
+        !self.horizontal[t % self.width][idx] && !self.vertical[t % self.height][idx]
     }
+}
 
-    (
-        walls,
-        blizzards,
-        P {
-            row: max_r,
-            col: max_c,
-        },
-        start.unwrap(),
-        end.unwrap(),
-    )
+/// One minute of the expedition's route.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Move {
+    Right,
+    Left,
+    Up,
+    Down,
+    Wait,
 }
 
-fn solve(
-    walls: HashSet<P>,
-    blizzards: HashSet<(P, P)>,
-    max: P,
-    start: P,
-    mut goals: Vec<P>,
-) -> usize {
-    let mut candidates = HashSet::new();
-    candidates.insert(start);
+impl Move {
+    const ALL: [Move; 5] = [Move::Right, Move::Left, Move::Up, Move::Down, Move::Wait];
 
-    for step in 0.. {
-        if goals.is_empty() {
-            return step - 1;
+    fn delta(self) -> P {
+        match self {
+            Move::Right => R,
+            Move::Left => L,
+            Move::Up => U,
+            Move::Down => D,
+            Move::Wait => NOOP,
         }
-        let blizzard_locs = blizzards
-            .iter()
-            .map(|(p, d)| (*p + P::from((step, step)) * *d).wrap(max))
-            .collect::<HashSet<_>>();
-        let next = candidates
+    }
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Move::Right => "R",
+            Move::Left => "L",
+            Move::Up => "U",
+            Move::Down => "D",
+            Move::Wait => "wait",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A search node: position, minute modulo the period, and number of waypoints
+/// reached.
+type State = (P, usize, usize);
+
+/// Finds routes through the valley. Positions are `(row, col)` in the input,
+/// so the entrance is always on row 0.
+pub struct Planner {
+    valley: Valley,
+    forecast: Forecast,
+}
+
+impl Planner {
+    pub fn new(valley: Valley) -> Self {
+        Planner {
+            forecast: Forecast::new(&valley.blizzards, valley.max),
+            valley,
+        }
+    }
+
+    pub fn parse(input: &str) -> Self {
+        Planner::new(Valley::parse(input))
+    }
+
+    pub fn valley(&self) -> &Valley {
+        &self.valley
+    }
+
+    pub fn entrance(&self) -> (usize, usize) {
+        self.valley.entrance()
+    }
+
+    pub fn exit(&self) -> (usize, usize) {
+        self.valley.exit()
+    }
+
+    /// Number of minutes after which the blizzards are back where they
+    /// started.
+    pub fn period(&self) -> usize {
+        self.forecast.period()
+    }
+
+    fn is_open(&self, p: P, t: usize) -> bool {
+        (-1..=self.valley.max.row).contains(&p.row)
+            && !self.valley.walls.contains(&p)
+            && self.forecast.is_clear(p, t)
+    }
+
+    /// The fastest route leaving the entrance at minute 0 and passing through
+    /// every waypoint in order, or `None` if the blizzards never allow it.
+    ///
+    /// This is a breadth-first search over `(position, minute % period,
+    /// waypoints reached)`, so it is optimal even when waiting at a waypoint
+    /// isn't safe.
+    pub fn route(&self, waypoints: &[(usize, usize)]) -> Option<Vec<Move>> {
+        let waypoints = waypoints
             .iter()
-            .flat_map(|p| [R, L, U, D, NOOP].map(|d| (*p + d)))
-            .collect::<HashSet<_>>();
-        candidates = next
-            .difference(&blizzard_locs)
-            .filter(|p| !walls.contains(p))
-            .copied()
-            .collect();
+            .map(|w| Valley::from_input(*w))
+            .collect::<Vec<_>>();
+        let period = self.period();
+
+        // Each state remembers the state it was reached from and how.
+        let mut parents: HashMap<State, Option<(State, Move)>> = HashMap::new();
+        let mut q = VecDeque::new();
+        parents.insert((self.valley.start, 0, 0), None);
+        q.push_back((self.valley.start, 0, 0));
+
+        while let Some((p, t, reached)) = q.pop_front() {
+            if reached == waypoints.len() {
+                let mut moves = vec![];
+                let mut state = (p, t % period, reached);
+                while let Some(Some((prev, m))) = parents.get(&state) {
+                    moves.push(*m);
+                    state = *prev;
+                }
+                moves.reverse();
+                return Some(moves);
+            }
 
-        if candidates.contains(&goals[0]) {
-            let v = goals.remove(0);
-            candidates = HashSet::new();
-            candidates.insert(v);
+            for m in Move::ALL {
+                let next = p + m.delta();
+                if !self.is_open(next, t + 1) {
+                    continue;
+                }
+                let now_reached = if next == waypoints[reached] {
+                    reached + 1
+                } else {
+                    reached
+                };
+                let key = (next, (t + 1) % period, now_reached);
+                if let Entry::Vacant(e) = parents.entry(key) {
+                    e.insert(Some(((p, t % period, reached), m)));
+                    q.push_back((next, t + 1, now_reached));
+                }
+            }
         }
+        None
     }
-    unreachable!()
 }
 
 pub fn part_1(input: &str) -> usize {
-    let (walls, blizzards, max, start, end) = parse(input);
-    solve(walls, blizzards, max, start, vec![end])
+    let planner = Planner::parse(input);
+    planner.route(&[planner.exit()]).unwrap().len()
 }
 
 pub fn part_2(input: &str) -> usize {
-    let (walls, blizzards, max, start, end) = parse(input);
-    solve(walls, blizzards, max, start, vec![end, start, end])
+    let planner = Planner::parse(input);
+    let (entrance, exit) = (planner.entrance(), planner.exit());
+    planner.route(&[exit, entrance, exit]).unwrap().len()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_24::{part_1, part_2};
+    use crate::day_24::{part_1, part_2, Move, Planner, Valley};
+    use std::collections::HashSet;
     const INPUTS: &str = r#"#.######
 #>>.<^<#
 #.<..<<#
@@ -193,4 +451,139 @@ pub mod tests {
     pub fn test_day_24_part2() {
         assert_eq!(part_2(include_str!("input/day_24.txt")), 713);
     }
+
+    // Replays a route with blizzards moved one minute at a time, returning
+    // the waypoints passed through.
+    fn replay(input: &str, moves: &[Move]) -> Vec<(usize, usize)> {
+        let Valley {
+            walls,
+            mut blizzards,
+            max,
+            start,
+            ..
+        } = Valley::parse(input);
+        let mut pos = start;
+        let mut visited = vec![];
+        for m in moves {
+            blizzards = blizzards
+                .into_iter()
+                .map(|(p, d)| ((p + d).wrap(max), d))
+                .collect::<HashSet<_>>();
+            pos = pos + m.delta();
+            assert!(!walls.contains(&pos) && pos.row <= max.row);
+            assert!(!blizzards.iter().any(|(p, _)| *p == pos));
+            visited.push(((pos.row + 1) as usize, (pos.col + 1) as usize));
+        }
+        visited
+    }
+
+    fn passes_through(visited: &[(usize, usize)], waypoints: &[(usize, usize)]) -> bool {
+        let mut it = visited.iter();
+        waypoints.iter().all(|w| it.any(|v| v == w))
+    }
+
+    #[test]
+    pub fn test_day_24_example_route() {
+        let planner = Planner::parse(INPUTS);
+        assert_eq!(planner.period(), 12);
+        assert_eq!(planner.entrance(), (0, 1));
+        assert_eq!(planner.exit(), (5, 6));
+
+        let waypoints = [planner.exit(), planner.entrance(), planner.exit()];
+        for n in 1..=3 {
+            let moves = planner.route(&waypoints[..n]).unwrap();
+            let visited = replay(INPUTS, &moves);
+            assert!(passes_through(&visited, &waypoints[..n]));
+            assert_eq!(visited.last(), Some(&waypoints[n - 1]));
+        }
+
+        let moves = planner.route(&[planner.exit()]).unwrap();
+        assert_eq!(moves.len(), 18);
+        assert_eq!(moves[0], Move::Down);
+        assert_eq!(moves.last(), Some(&Move::Down));
+        assert_eq!(Move::Wait.to_string(), "wait");
+    }
+
+    #[test]
+    pub fn test_day_24_interior_waypoints() {
+        let planner = Planner::parse(INPUTS);
+        let waypoints = [(4, 1), (1, 6), planner.exit()];
+        let moves = planner.route(&waypoints).unwrap();
+        assert!(passes_through(&replay(INPUTS, &moves), &waypoints));
+
+        // Detouring through the waypoints can't beat going straight there.
+        let direct = planner.route(&[planner.exit()]).unwrap().len();
+        assert!(moves.len() >= direct);
+
+        let input = include_str!("input/day_24.txt");
+        let planner = Planner::parse(input);
+        let waypoints = [planner.exit(), planner.entrance(), planner.exit()];
+        let moves = planner.route(&waypoints).unwrap();
+        assert_eq!(moves.len(), 713);
+        assert!(passes_through(&replay(input, &moves), &waypoints));
+
+        // Walls are never reachable.
+        assert_eq!(planner.route(&[(0, 0)]), None);
+    }
+
+    // Every map drawn in the prompt, with the line of text just before it.
+    fn prompt_maps() -> Vec<(&'static str, String)> {
+        let mut maps = vec![];
+        let mut header = "";
+        let mut map: Vec<&str> = vec![];
+        for line in include_str!("day_24_prompt.txt").lines() {
+            if line.starts_with('#') {
+                map.push(line);
+                continue;
+            }
+            if !map.is_empty() {
+                maps.push((header, map.join("\n")));
+                map.clear();
+            }
+            if !line.is_empty() {
+                header = line;
+            }
+        }
+        maps
+    }
+
+    #[test]
+    pub fn test_day_24_valley_matches_prompt() {
+        let maps = prompt_maps();
+
+        let valley = Valley::parse(&maps[0].1);
+        for (minute, (_, map)) in maps[..6].iter().enumerate() {
+            assert_eq!(&valley.state_at(minute).to_string(), map);
+        }
+
+        let valley = Valley::parse(INPUTS);
+        assert_eq!(valley.state_at(0).to_string(), INPUTS);
+        assert_eq!(valley.state_at(12).to_string(), INPUTS);
+        assert_eq!(valley.state_at(1).get((1, 3)), Some('3'));
+
+        let mut pos = valley.entrance();
+        let mut minutes = 0;
+        for (header, map) in maps.iter().filter(|(h, _)| h.starts_with("Minute")) {
+            let (minute, action) = header
+                .trim_start_matches("Minute ")
+                .trim_end_matches(':')
+                .split_once(", ")
+                .unwrap();
+            match action {
+                "move up" => pos.0 -= 1,
+                "move down" => pos.0 += 1,
+                "move left" => pos.1 -= 1,
+                "move right" => pos.1 += 1,
+                _ => (),
+            }
+            let minute = minute.parse().unwrap();
+            assert_eq!(
+                &valley.state_at(minute).with_expedition(pos).to_string(),
+                map
+            );
+            minutes = minute;
+        }
+        assert_eq!(minutes, 18);
+        assert_eq!(pos, valley.exit());
+    }
 }
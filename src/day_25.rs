@@ -130,42 +130,12 @@
 
 // The Elves are starting to get cold. What SNAFU number do you supply to Bob's console?
 
-fn to_snafu(v: isize) -> String {
-    if v == 0 {
-        String::new()
-    } else {
-        let mut s = to_snafu((v + 2) / 5);
-        s.push(['0', '1', '2', '=', '-'][v.rem_euclid(5) as usize]);
-        s
-    }
-}
-
-fn from_snafu(s: &str) -> isize {
-    let mut v = 0;
-    for c in s.chars() {
-        v = v * 5
-            + match c {
-                '2' => 2,
-                '1' => 1,
-                '0' => 0,
-                '-' => -1,
-                '=' => -2,
-                _ => unreachable!(),
-            };
-    }
-    v
-}
+use crate::day_25_balanced::Radix;
 
 pub fn part_1(input: &str) -> String {
-    let mut sum = 0;
-    for line in input.lines() {
-        if line.is_empty() {
-            continue;
-        }
-        sum += from_snafu(line);
-    }
-
-    to_snafu(sum)
+    Radix::snafu()
+        .sum(input.lines().filter(|l| !l.is_empty()))
+        .unwrap()
 }
 
 #[cfg(test)]
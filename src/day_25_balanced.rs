@@ -0,0 +1,370 @@
+// Numbers in a balanced base, where the digits run from `-(base / 2)` to
+// `base / 2` so that no sign is needed. SNAFU is balanced base 5.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RadixError {
+    /// Balanced bases need an odd number of digits, at least three and
+    /// fewer than 2^32 so that multiplying digits can't overflow.
+    BadBase(usize),
+    DuplicateDigit(char),
+    /// An empty string isn't a number; zero is written with the zero digit.
+    Empty,
+    /// `position` counts characters from the left.
+    InvalidDigit {
+        position: usize,
+        digit: char,
+    },
+    BaseMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// An arbitrary-precision value in some balanced base.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Number {
+    base: i64,
+    /// Least significant first, without leading zeros, so zero has none.
+    digits: Vec<i64>,
+}
+
+fn check_base(base: usize) -> Result<i64, RadixError> {
+    if base < 3 || base.is_multiple_of(2) || base > u32::MAX as usize {
+        return Err(RadixError::BadBase(base));
+    }
+    Ok(base as i64)
+}
+
+impl Number {
+    /// Builds a number from digits which may be out of range, carrying into
+    /// higher places until they aren't.
+    fn normalize(base: i64, mut digits: Vec<i64>) -> Number {
+        let half = base / 2;
+        let mut carry = 0;
+        let mut idx = 0;
+        while idx < digits.len() || carry != 0 {
+            if idx == digits.len() {
+                digits.push(0);
+            }
+            let v = digits[idx] + carry;
+            let mut r = v.rem_euclid(base);
+            if r > half {
+                r -= base;
+            }
+            digits[idx] = r;
+            carry = (v - r) / base;
+            idx += 1;
+        }
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Number { base, digits }
+    }
+
+    fn empty(base: i64) -> Number {
+        Number {
+            base,
+            digits: vec![],
+        }
+    }
+
+    pub fn zero(base: usize) -> Result<Number, RadixError> {
+        Ok(Number::empty(check_base(base)?))
+    }
+
+    pub fn from_i128(v: i128, base: usize) -> Result<Number, RadixError> {
+        let base = check_base(base)?;
+        let b = base as i128;
+        let mut digits = vec![];
+        let mut v = v;
+        while v != 0 {
+            let (mut q, mut r) = (v.div_euclid(b), v.rem_euclid(b));
+            if r > b / 2 {
+                r -= b;
+                q += 1;
+            }
+            digits.push(r as i64);
+            v = q;
+        }
+        Ok(Number { base, digits })
+    }
+
+    /// The value as an `i128`, or `None` if it doesn't fit.
+    pub fn to_i128(&self) -> Option<i128> {
+        let base = self.base as i128;
+        self.digits.iter().rev().try_fold(0i128, |acc, d| {
+            let d = *d as i128;
+            // A digit with the opposite sign can pull `acc * base` back into
+            // range, so borrow one from `acc` to avoid overflowing early.
+            let (acc, d) = match (acc.signum(), d.signum()) {
+                (-1, 1) => (acc + 1, d - base),
+                (1, -1) => (acc - 1, d + base),
+                _ => (acc, d),
+            };
+            acc.checked_mul(base)?.checked_add(d)
+        })
+    }
+
+    pub fn base(&self) -> usize {
+        self.base as usize
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// The sign of the value is the sign of its leading digit.
+    pub fn signum(&self) -> i64 {
+        self.digits.last().map_or(0, |d| d.signum())
+    }
+
+    pub fn neg(&self) -> Number {
+        Number {
+            base: self.base,
+            digits: self.digits.iter().map(|d| -d).collect(),
+        }
+    }
+
+    /// `None` if the numbers are in different bases.
+    pub fn checked_add(&self, other: &Number) -> Option<Number> {
+        if self.base != other.base {
+            return None;
+        }
+        let len = self.digits.len().max(other.digits.len());
+        let digits = (0..len)
+            .map(|i| self.digits.get(i).unwrap_or(&0) + other.digits.get(i).unwrap_or(&0))
+            .collect();
+        Some(Number::normalize(self.base, digits))
+    }
+
+    pub fn checked_sub(&self, other: &Number) -> Option<Number> {
+        self.checked_add(&other.neg())
+    }
+
+    pub fn checked_mul(&self, other: &Number) -> Option<Number> {
+        if self.base != other.base {
+            return None;
+        }
+        if self.is_zero() || other.is_zero() {
+            return Some(Number::empty(self.base));
+        }
+        // Normalizing after each row keeps the sums from overflowing however
+        // long the numbers are.
+        let mut product = Number::empty(self.base);
+        for (shift, a) in self.digits.iter().enumerate() {
+            let mut row = vec![0; shift];
+            row.extend(other.digits.iter().map(|b| a * b));
+            product = product.checked_add(&Number::normalize(self.base, row))?;
+        }
+        Some(product)
+    }
+}
+
+/// The characters used to write a balanced base, from the most negative
+/// digit to the most positive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Radix {
+    alphabet: Vec<char>,
+}
+
+impl Radix {
+    pub fn new(alphabet: &str) -> Result<Radix, RadixError> {
+        let alphabet = alphabet.chars().collect::<Vec<_>>();
+        check_base(alphabet.len())?;
+        for (idx, c) in alphabet.iter().enumerate() {
+            if alphabet[..idx].contains(c) {
+                return Err(RadixError::DuplicateDigit(*c));
+            }
+        }
+        Ok(Radix { alphabet })
+    }
+
+    /// Base 5 with digits `=-012`.
+    pub fn snafu() -> Radix {
+        Radix::new("=-012").unwrap()
+    }
+
+    pub fn base(&self) -> usize {
+        self.alphabet.len()
+    }
+
+    fn half(&self) -> i64 {
+        self.base() as i64 / 2
+    }
+
+    pub fn parse(&self, s: &str) -> Result<Number, RadixError> {
+        if s.is_empty() {
+            return Err(RadixError::Empty);
+        }
+        let mut digits = s
+            .chars()
+            .enumerate()
+            .map(|(position, digit)| {
+                self.alphabet
+                    .iter()
+                    .position(|c| *c == digit)
+                    .map(|idx| idx as i64 - self.half())
+                    .ok_or(RadixError::InvalidDigit { position, digit })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        digits.reverse();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        Ok(Number {
+            base: self.base() as i64,
+            digits,
+        })
+    }
+
+    pub fn format(&self, n: &Number) -> Result<String, RadixError> {
+        if n.base() != self.base() {
+            return Err(RadixError::BaseMismatch {
+                expected: self.base(),
+                found: n.base(),
+            });
+        }
+        if n.is_zero() {
+            return Ok(self.alphabet[self.half() as usize].to_string());
+        }
+        Ok(n.digits
+            .iter()
+            .rev()
+            .map(|d| self.alphabet[(d + self.half()) as usize])
+            .collect())
+    }
+
+    pub fn from_i128(&self, v: i128) -> String {
+        self.format(&Number::from_i128(v, self.base()).unwrap())
+            .unwrap()
+    }
+
+    fn apply(
+        &self,
+        a: &str,
+        b: &str,
+        op: impl Fn(&Number, &Number) -> Option<Number>,
+    ) -> Result<String, RadixError> {
+        let (a, b) = (self.parse(a)?, self.parse(b)?);
+        self.format(&op(&a, &b).unwrap())
+    }
+
+    pub fn add(&self, a: &str, b: &str) -> Result<String, RadixError> {
+        self.apply(a, b, Number::checked_add)
+    }
+
+    pub fn sub(&self, a: &str, b: &str) -> Result<String, RadixError> {
+        self.apply(a, b, Number::checked_sub)
+    }
+
+    pub fn mul(&self, a: &str, b: &str) -> Result<String, RadixError> {
+        self.apply(a, b, Number::checked_mul)
+    }
+
+    /// Adds up any number of digit strings; the sum of none is zero.
+    pub fn sum<'a>(&self, items: impl IntoIterator<Item = &'a str>) -> Result<String, RadixError> {
+        let mut total = Number::empty(self.base() as i64);
+        for item in items {
+            total = total.checked_add(&self.parse(item)?).unwrap();
+        }
+        self.format(&total)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::day_25_balanced::{Number, Radix, RadixError};
+
+    #[test]
+    pub fn test_day_25_balanced_snafu_brochure() {
+        let snafu = Radix::snafu();
+        for (decimal, digits) in [
+            (0, "0"),
+            (1, "1"),
+            (2, "2"),
+            (3, "1="),
+            (4, "1-"),
+            (5, "10"),
+            (8, "2="),
+            (15, "1=0"),
+            (2022, "1=11-2"),
+            (12345, "1-0---0"),
+            (314159265, "1121-1110-1=0"),
+        ] {
+            assert_eq!(snafu.from_i128(decimal), digits);
+            assert_eq!(snafu.parse(digits).unwrap().to_i128(), Some(decimal));
+            assert_eq!(snafu.from_i128(-decimal), snafu.sub("0", digits).unwrap());
+        }
+        assert_eq!(snafu.parse("000").unwrap(), Number::zero(5).unwrap());
+        assert_eq!(snafu.parse("0012").unwrap().to_i128(), Some(7));
+    }
+
+    #[test]
+    pub fn test_day_25_balanced_arithmetic() {
+        for alphabet in ["-0+", "=-012", "abcdefghi", "ABCDEFGHIJKLM"] {
+            let radix = Radix::new(alphabet).unwrap();
+            for a in [-1000i128, -37, -1, 0, 1, 2, 99, 4096] {
+                for b in [-81i128, -2, 0, 1, 3, 250] {
+                    let (x, y) = (radix.from_i128(a), radix.from_i128(b));
+                    assert_eq!(radix.add(&x, &y).unwrap(), radix.from_i128(a + b));
+                    assert_eq!(radix.sub(&x, &y).unwrap(), radix.from_i128(a - b));
+                    assert_eq!(radix.mul(&x, &y).unwrap(), radix.from_i128(a * b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_25_balanced_arbitrary_precision() {
+        let snafu = Radix::snafu();
+        let big = snafu.from_i128(i128::MAX);
+        assert_eq!(snafu.parse(&big).unwrap().to_i128(), Some(i128::MAX));
+        let squared = snafu.mul(&big, &big).unwrap();
+        assert_eq!(snafu.parse(&squared).unwrap().to_i128(), None);
+        assert_eq!(snafu.sub(&squared, &squared).unwrap(), "0");
+
+        let min = Number::from_i128(i128::MIN, 5).unwrap();
+        assert_eq!(min.to_i128(), Some(i128::MIN));
+        assert_eq!(min.signum(), -1);
+
+        // (2^127 - 1)^2 + 2 * (2^127 - 1) + 1 = 2^254
+        let one = snafu.from_i128(1);
+        let plus_one = snafu.add(&big, &one).unwrap();
+        let expected = (0..254).fold("1".to_string(), |acc, _| snafu.add(&acc, &acc).unwrap());
+        assert_eq!(snafu.mul(&plus_one, &plus_one).unwrap(), expected);
+    }
+
+    #[test]
+    pub fn test_day_25_balanced_errors() {
+        assert_eq!(Radix::new("01"), Err(RadixError::BadBase(2)));
+        assert_eq!(Radix::new("0"), Err(RadixError::BadBase(1)));
+        assert_eq!(Radix::new("a-a"), Err(RadixError::DuplicateDigit('a')));
+        for base in [0, 1, 2, 4, 1 << 40] {
+            assert_eq!(Number::zero(base), Err(RadixError::BadBase(base)));
+            assert_eq!(Number::from_i128(-7, base), Err(RadixError::BadBase(base)));
+        }
+        assert_eq!(Number::from_i128(-7, 7).unwrap().to_i128(), Some(-7));
+
+        let snafu = Radix::snafu();
+        assert_eq!(snafu.parse(""), Err(RadixError::Empty));
+        assert_eq!(
+            snafu.add("1=", "13"),
+            Err(RadixError::InvalidDigit {
+                position: 1,
+                digit: '3'
+            })
+        );
+
+        let ternary = Radix::new("-0+").unwrap();
+        let n = ternary.parse("+-").unwrap();
+        assert_eq!(n.to_i128(), Some(2));
+        assert_eq!(
+            snafu.format(&n),
+            Err(RadixError::BaseMismatch {
+                expected: 5,
+                found: 3
+            })
+        );
+        assert_eq!(n.checked_add(&snafu.parse("2").unwrap()), None);
+    }
+}
@@ -139,127 +139,377 @@
 // After the rearrangement procedure completes, what crate ends up on top of
 // each stack?
 
-use std::collections::HashMap;
-
-struct State {
-    stacks: HashMap<usize, Vec<char>>,
-    lookups: HashMap<char, usize>,
+/// A crate's label, which can be more than one character.
+pub type Crate = String;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawingError {
+    /// The drawing has no line of stack numbers under it.
+    NoFooter,
+    DuplicateStack(usize),
+    /// Lines and columns count from 1.
+    Unclosed {
+        line: usize,
+        column: usize,
+    },
+    /// Something other than a crate in the drawing, including `[]`.
+    Stray {
+        line: usize,
+        column: usize,
+    },
 }
 
-impl State {
-    pub fn parse<'a, I: Iterator<Item = &'a str> + Sized>(input: &mut I) -> State {
-        let mut stacks: HashMap<usize, Vec<char>> = HashMap::new();
-        let mut revlabels: HashMap<char, usize> = HashMap::new();
+/// The stacks in the order they're numbered in the drawing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stacks {
+    ids: Vec<usize>,
+    /// Bottom crate first.
+    crates: Vec<Vec<Crate>>,
+}
 
+impl Stacks {
+    /// Reads the drawing up to and including its numbered footer line.
+    ///
+    /// Each crate goes on the stack whose number is closest to the middle of
+    /// it, so labels and stack numbers can be any width as long as they're
+    /// roughly lined up, and trailing whitespace doesn't matter.
+    pub fn parse<'a, I: Iterator<Item = &'a str> + Sized>(
+        input: &mut I,
+    ) -> Result<Stacks, DrawingError> {
+        let mut rows = vec![];
+        let mut footer = None;
         for line in input.by_ref() {
-            if line.is_empty() {
-                continue;
+            let chars = line.chars().collect::<Vec<_>>();
+            if let Some(ids) = Stacks::footer(&chars) {
+                footer = Some(ids);
+                break;
             }
+            rows.push(chars);
+        }
+        // (id, twice the column of its middle)
+        let footer = footer.ok_or(DrawingError::NoFooter)?;
+        let ids = footer.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        for (idx, id) in ids.iter().enumerate() {
+            if ids[..idx].contains(id) {
+                return Err(DrawingError::DuplicateStack(*id));
+            }
+        }
 
-            let mut opened = None;
-            let mut parsed_number = false;
-            let mut label = ' ';
-
-            for (idx, char) in line.chars().enumerate() {
-                match char {
-                    '[' => opened = Some(idx + 1),
-                    ']' if opened.is_some() => {
-                        stacks.entry(opened.unwrap()).or_default().push(label);
-                        opened = None;
+        let mut crates = vec![vec![]; ids.len()];
+        for (row, chars) in rows.iter().enumerate().rev() {
+            let mut col = 0;
+            while col < chars.len() {
+                match chars[col] {
+                    c if c.is_whitespace() => col += 1,
+                    '[' => {
+                        let len = chars[col..].iter().position(|c| *c == ']').ok_or(
+                            DrawingError::Unclosed {
+                                line: row + 1,
+                                column: col + 1,
+                            },
+                        )?;
+                        if len == 1 {
+                            return Err(DrawingError::Stray {
+                                line: row + 1,
+                                column: col + 1,
+                            });
+                        }
+                        let middle = 2 * col + len;
+                        let stack = (0..footer.len())
+                            .min_by_key(|i| footer[*i].1.abs_diff(middle))
+                            .unwrap();
+                        crates[stack].push(chars[col + 1..col + len].iter().collect());
+                        col += len + 1;
                     }
-                    _ if opened.is_some() => label = char,
-                    a if a.is_numeric() => {
-                        revlabels.insert(a, idx);
-                        parsed_number = true;
+                    _ => {
+                        return Err(DrawingError::Stray {
+                            line: row + 1,
+                            column: col + 1,
+                        })
                     }
-                    _ => continue,
                 }
             }
+        }
 
-            if parsed_number {
-                break;
+        Ok(Stacks { ids, crates })
+    }
+
+    /// The stack numbers and where they are, if this is the footer line.
+    fn footer(chars: &[char]) -> Option<Vec<(usize, usize)>> {
+        let mut ids = vec![];
+        let mut col = 0;
+        while col < chars.len() {
+            if chars[col].is_whitespace() {
+                col += 1;
+                continue;
             }
+            let len = chars[col..]
+                .iter()
+                .position(|c| c.is_whitespace())
+                .unwrap_or(chars.len() - col);
+            let token = chars[col..col + len].iter().collect::<String>();
+            ids.push((token.parse().ok()?, 2 * col + len - 1));
+            col += len;
         }
-        stacks.values_mut().for_each(|v| v.reverse());
+        (!ids.is_empty()).then_some(ids)
+    }
 
-        State {
-            stacks,
-            lookups: revlabels,
-        }
+    pub fn ids(&self) -> &[usize] {
+        &self.ids
     }
 
-    fn get(&mut self, name: char) -> &'_ mut Vec<char> {
-        self.stacks.get_mut(&self.lookups[&name]).unwrap()
+    /// The crates on stack `id`, bottom first.
+    pub fn get(&self, id: usize) -> Option<&[Crate]> {
+        let idx = self.ids.iter().position(|i| *i == id)?;
+        Some(&self.crates[idx])
     }
 
-    fn top(&self) -> String {
-        let mut keys = self.lookups.keys().collect::<Vec<_>>();
-        keys.sort();
+    fn index(&self, id: usize) -> Result<usize, CraneError> {
+        self.ids
+            .iter()
+            .position(|i| *i == id)
+            .ok_or(CraneError::UnknownStack(id))
+    }
 
-        keys.into_iter()
-            .map(|label| *self.stacks[&self.lookups[label]].last().unwrap())
+    /// The top crate of every stack, skipping empty ones.
+    pub fn top(&self) -> String {
+        self.crates
+            .iter()
+            .filter_map(|v| v.last())
+            .cloned()
             .collect()
     }
 }
 
-fn parse_cmd(line: &str) -> (usize, char, char) {
-    let mut cmd_str_iter = line.split_ascii_whitespace();
-    assert_eq!(cmd_str_iter.next().unwrap(), "move");
-    let num = cmd_str_iter.next().unwrap().parse::<usize>().unwrap();
-    assert_eq!(cmd_str_iter.next().unwrap(), "from");
-    let from = cmd_str_iter.next().unwrap().chars().next().unwrap();
-    assert_eq!(cmd_str_iter.next().unwrap(), "to");
-    let to = cmd_str_iter.next().unwrap().chars().next().unwrap();
-    (num, from, to)
+/// Draws the stacks the way the puzzle does, without trailing whitespace.
+/// Columns are widened to fit the longest label or stack number.
+impl std::fmt::Display for Stacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .crates
+            .iter()
+            .flatten()
+            .map(|c| c.chars().count() + 2)
+            .chain(self.ids.iter().map(|id| id.to_string().len()))
+            .fold(3, usize::max);
+        let cell = |s: String| {
+            let pad = width - s.chars().count();
+            format!("{}{}{}", " ".repeat(pad / 2), s, " ".repeat(pad - pad / 2))
+        };
+        let mut lines = vec![];
+        let height = self.crates.iter().map(|v| v.len()).max().unwrap_or(0);
+        for row in (0..height).rev() {
+            lines.push(
+                self.crates
+                    .iter()
+                    .map(|v| cell(v.get(row).map_or(String::new(), |c| format!("[{}]", c))))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        lines.push(self.ids.iter().map(|id| cell(id.to_string())).collect());
+        for line in lines {
+            writeln!(f, "{}", line.join(" ").trim_end())?;
+        }
+        Ok(())
+    }
 }
 
-pub fn part_1(input: &str) -> String {
-    let mut iter = input.lines();
-    let mut s = State::parse(&mut iter);
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub count: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl Move {
+    /// Parses `move 1 from 2 to 1`.
+    pub fn parse(line: &str) -> Option<Move> {
+        let mut cmd_str_iter = line.split_ascii_whitespace();
+        let mut field = |name: &str| {
+            if cmd_str_iter.next()? != name {
+                return None;
+            }
+            cmd_str_iter.next()?.parse::<usize>().ok()
+        };
+        let m = Move {
+            count: field("move")?,
+            from: field("from")?,
+            to: field("to")?,
+        };
+        cmd_str_iter.next().is_none().then_some(m)
+    }
 
-    for line in iter {
-        if line.is_empty() {
-            continue;
+    /// The move that puts the crates back. Both cranes can undo a move by
+    /// running it in reverse, since each of them either flips the lifted
+    /// crates or doesn't.
+    pub fn reversed(self) -> Move {
+        Move {
+            count: self.count,
+            from: self.to,
+            to: self.from,
         }
+    }
+}
 
-        let (num, from, to) = parse_cmd(line);
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
 
-        for _ in 0..num {
-            let v = s.get(from).pop().unwrap();
-            s.get(to).push(v);
-        }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CraneError {
+    UnknownStack(usize),
+    NotEnoughCrates {
+        stack: usize,
+        has: usize,
+        wanted: usize,
+    },
+    NothingToUndo,
+    NothingToRedo,
+    Drawing(DrawingError),
+    /// `line` counts from 1, from the top of the input.
+    BadMove(usize),
+}
+
+pub trait Crane {
+    /// Takes the top `count` crates off `from` and puts them on `to`.
+    /// `from` always has enough crates.
+    fn transfer(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, count: usize);
+}
+
+/// Moves crates one at a time, so they land in reverse order.
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn transfer(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, count: usize) {
+        to.extend(from.drain(from.len() - count..).rev());
     }
+}
+
+/// Moves all the crates at once, keeping their order.
+pub struct CrateMover9001;
 
-    s.top()
+impl Crane for CrateMover9001 {
+    fn transfer(&self, from: &mut Vec<Crate>, to: &mut Vec<Crate>, count: usize) {
+        to.extend(from.drain(from.len() - count..));
+    }
 }
 
-pub fn part_2(input: &str) -> String {
-    let mut iter = input.lines();
-    let mut s = State::parse(&mut iter);
-    for line in iter {
-        if line.is_empty() {
-            continue;
+/// A crane working on a set of stacks, remembering what it did so that moves
+/// can be undone and redone.
+pub struct Machine<C: Crane> {
+    stacks: Stacks,
+    crane: C,
+    done: Vec<Move>,
+    undone: Vec<Move>,
+}
+
+impl<C: Crane> Machine<C> {
+    pub fn new(stacks: Stacks, crane: C) -> Self {
+        Machine {
+            stacks,
+            crane,
+            done: vec![],
+            undone: vec![],
         }
+    }
 
-        let (num, from, to) = parse_cmd(line);
+    pub fn stacks(&self) -> &Stacks {
+        &self.stacks
+    }
 
-        let mut to_xfer = vec![];
+    /// The moves made so far, oldest first, not counting undone ones.
+    pub fn history(&self) -> &[Move] {
+        &self.done
+    }
 
-        for _ in 0..num {
-            to_xfer.push(s.get(from).pop().unwrap());
+    fn perform(&mut self, m: Move) -> Result<(), CraneError> {
+        let from = self.stacks.index(m.from)?;
+        let to = self.stacks.index(m.to)?;
+        let has = self.stacks.crates[from].len();
+        if has < m.count {
+            return Err(CraneError::NotEnoughCrates {
+                stack: m.from,
+                has,
+                wanted: m.count,
+            });
+        }
+        if from != to {
+            let (src, dst) = if from < to {
+                let (a, b) = self.stacks.crates.split_at_mut(to);
+                (&mut a[from], &mut b[0])
+            } else {
+                let (a, b) = self.stacks.crates.split_at_mut(from);
+                (&mut b[0], &mut a[to])
+            };
+            self.crane.transfer(src, dst, m.count);
         }
+        Ok(())
+    }
+
+    /// Makes a move, forgetting anything that was undone. The stacks are left
+    /// alone if the move is invalid.
+    pub fn apply(&mut self, m: Move) -> Result<(), CraneError> {
+        self.perform(m)?;
+        self.done.push(m);
+        self.undone.clear();
+        Ok(())
+    }
 
-        for v in to_xfer.into_iter().rev() {
-            s.get(to).push(v);
+    pub fn undo(&mut self) -> Result<Move, CraneError> {
+        let m = self.done.pop().ok_or(CraneError::NothingToUndo)?;
+        self.perform(m.reversed()).unwrap();
+        self.undone.push(m);
+        Ok(m)
+    }
+
+    pub fn redo(&mut self) -> Result<Move, CraneError> {
+        let m = self.undone.pop().ok_or(CraneError::NothingToRedo)?;
+        self.perform(m).unwrap();
+        self.done.push(m);
+        Ok(m)
+    }
+
+    /// Runs every move after the drawing.
+    pub fn run<'a>(
+        &mut self,
+        lines: impl Iterator<Item = (usize, &'a str)>,
+    ) -> Result<(), CraneError> {
+        for (idx, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            self.apply(Move::parse(line).ok_or(CraneError::BadMove(idx + 1))?)?;
         }
+        Ok(())
     }
+}
 
-    s.top()
+/// Parses the drawing and runs the procedure with the given crane.
+pub fn rearrange<C: Crane>(input: &str, crane: C) -> Result<Machine<C>, CraneError> {
+    let mut iter = input.lines().enumerate();
+    let mut lines = iter.by_ref().map(|(_, line)| line);
+    let stacks = Stacks::parse(&mut lines).map_err(CraneError::Drawing)?;
+    let mut machine = Machine::new(stacks, crane);
+    machine.run(iter)?;
+    Ok(machine)
+}
+
+pub fn part_1(input: &str) -> String {
+    rearrange(input, CrateMover9000).unwrap().stacks().top()
+}
+
+pub fn part_2(input: &str) -> String {
+    rearrange(input, CrateMover9001).unwrap().stacks().top()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_5::{part_1, part_2};
+    use crate::day_5::{
+        part_1, part_2, rearrange, CraneError, CrateMover9000, CrateMover9001, DrawingError,
+        Machine, Move, Stacks,
+    };
 
     const INPUTS: &str = r#"    [D]    
 [N] [C]    
@@ -290,4 +540,214 @@ move 1 from 1 to 2"#;
     pub fn test_day_5_part2() {
         assert_eq!(part_2(include_str!("input/day_5.txt")), "LLWJRBHVZ");
     }
+
+    fn machine<C: crate::day_5::Crane>(crane: C) -> Machine<C> {
+        Machine::new(Stacks::parse(&mut INPUTS.lines()).unwrap(), crane)
+    }
+
+    // The drawings from the prompt after each step, for each crane.
+    const FRAMES_9000: [&str; 4] = [
+        "[D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n",
+        "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3\n",
+        "        [Z]\n        [N]\n[M]     [D]\n[C]     [P]\n 1   2   3\n",
+        "        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3\n",
+    ];
+    const FRAMES_9001: [&str; 4] = [
+        "[D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n",
+        "        [D]\n        [N]\n    [C] [Z]\n    [M] [P]\n 1   2   3\n",
+        "        [D]\n        [N]\n[C]     [Z]\n[M]     [P]\n 1   2   3\n",
+        "        [D]\n        [N]\n        [Z]\n[M] [C] [P]\n 1   2   3\n",
+    ];
+
+    #[test]
+    pub fn test_day_5_rendering_matches_prompt() {
+        let moves = INPUTS
+            .lines()
+            .skip(5)
+            .map(|l| Move::parse(l).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut m = machine(CrateMover9000);
+        assert_eq!(
+            m.stacks().to_string(),
+            "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n"
+        );
+        for (mv, frame) in moves.iter().zip(FRAMES_9000) {
+            m.apply(*mv).unwrap();
+            assert_eq!(m.stacks().to_string(), frame);
+        }
+
+        let mut m = machine(CrateMover9001);
+        for (mv, frame) in moves.iter().zip(FRAMES_9001) {
+            m.apply(*mv).unwrap();
+            assert_eq!(m.stacks().to_string(), frame);
+        }
+
+        // What we draw parses back to the same stacks.
+        let drawn = m.stacks().to_string();
+        assert_eq!(&Stacks::parse(&mut drawn.lines()).unwrap(), m.stacks());
+        assert_eq!(
+            Move::parse("move 12 from 3 to 10").unwrap().to_string(),
+            "move 12 from 3 to 10"
+        );
+    }
+
+    #[test]
+    pub fn test_day_5_undo_redo() {
+        let start = machine(CrateMover9000).stacks().clone();
+        let mut m = rearrange(INPUTS, CrateMover9000).unwrap();
+        assert_eq!(m.history().len(), 4);
+
+        for frame in FRAMES_9000[..3].iter().rev() {
+            m.undo().unwrap();
+            assert_eq!(m.stacks().to_string(), *frame);
+        }
+        assert_eq!(
+            m.undo(),
+            Ok(Move {
+                count: 1,
+                from: 2,
+                to: 1
+            })
+        );
+        assert_eq!(m.stacks(), &start);
+        assert_eq!(m.undo(), Err(CraneError::NothingToUndo));
+
+        m.redo().unwrap();
+        m.redo().unwrap();
+        assert_eq!(m.stacks().to_string(), FRAMES_9000[1]);
+
+        // A fresh move drops the rest of the redo stack.
+        m.apply(Move {
+            count: 1,
+            from: 3,
+            to: 1,
+        })
+        .unwrap();
+        assert_eq!(m.redo(), Err(CraneError::NothingToRedo));
+        assert_eq!(m.stacks().top(), "ZCN");
+
+        let mut m = rearrange(INPUTS, CrateMover9001).unwrap();
+        while m.undo().is_ok() {}
+        assert_eq!(m.stacks(), &start);
+    }
+
+    #[test]
+    pub fn test_day_5_invalid_moves() {
+        let mut m = machine(CrateMover9001);
+        assert_eq!(
+            m.apply(Move {
+                count: 3,
+                from: 3,
+                to: 1
+            }),
+            Err(CraneError::NotEnoughCrates {
+                stack: 3,
+                has: 1,
+                wanted: 3
+            })
+        );
+        assert_eq!(
+            m.apply(Move {
+                count: 1,
+                from: 4,
+                to: 1
+            }),
+            Err(CraneError::UnknownStack(4))
+        );
+        assert!(m.history().is_empty());
+        assert_eq!(m.stacks().top(), "NDP");
+
+        // Emptying a stack leaves it out of the top row.
+        m.apply(Move {
+            count: 1,
+            from: 3,
+            to: 2,
+        })
+        .unwrap();
+        assert_eq!(m.stacks().top(), "NP");
+        assert_eq!(m.stacks().get(3), Some(&[][..]));
+
+        assert_eq!(
+            rearrange(&INPUTS.replace("move 3", "move 4"), CrateMover9000).err(),
+            Some(CraneError::NotEnoughCrates {
+                stack: 1,
+                has: 3,
+                wanted: 4
+            })
+        );
+        assert_eq!(
+            rearrange(
+                &INPUTS.replace("from 2 to 1\nmove 3", "from 2 to 1\nmove three"),
+                CrateMover9000
+            )
+            .err(),
+            Some(CraneError::BadMove(7))
+        );
+    }
+
+    #[test]
+    pub fn test_day_5_drawing_parser() {
+        // Editors trimming trailing whitespace shouldn't change anything.
+        let trimmed = INPUTS
+            .lines()
+            .map(|l| l.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(part_1(&trimmed), "CMZ");
+        assert_eq!(part_2(&trimmed), "MCD");
+
+        let wide = "\
+[A]                                         [K]
+[B] [C] [D] [E] [F] [G] [H] [I] [J]     [L] [M]
+ 1   2   3   4   5   6   7   8   9   10  11  12
+
+move 2 from 12 to 10
+move 1 from 1 to 11";
+        let m = rearrange(wide, CrateMover9000).unwrap();
+        assert_eq!(m.stacks().ids(), (1..=12).collect::<Vec<_>>());
+        assert_eq!(m.stacks().top(), "BCDEFGHIJMA");
+        assert_eq!(m.stacks().get(10).unwrap(), ["K", "M"]);
+
+        let labels = "     [XY]\n[AB] [CD]  [E]\n  1    2    3";
+        let stacks = Stacks::parse(&mut labels.lines()).unwrap();
+        assert_eq!(stacks.get(2).unwrap(), ["CD", "XY"]);
+        assert_eq!(stacks.get(3).unwrap(), ["E"]);
+        let drawn = stacks.to_string();
+        assert_eq!(drawn, "     [XY]\n[AB] [CD] [E]\n 1    2    3\n");
+        assert_eq!(Stacks::parse(&mut drawn.lines()).unwrap(), stacks);
+
+        let mut m = Machine::new(stacks, CrateMover9001);
+        m.apply(Move::parse("move 2 from 2 to 3").unwrap()).unwrap();
+        assert_eq!(m.stacks().top(), "ABXY");
+    }
+
+    #[test]
+    pub fn test_day_5_drawing_errors() {
+        let parse = |s: &str| Stacks::parse(&mut s.lines());
+        assert_eq!(
+            parse("[A]\n\nmove 1 from 1 to 1"),
+            Err(DrawingError::NoFooter)
+        );
+        assert_eq!(
+            parse("[A] [B]\n 1   1"),
+            Err(DrawingError::DuplicateStack(1))
+        );
+        assert_eq!(
+            parse("[A] [B\n 1   2"),
+            Err(DrawingError::Unclosed { line: 1, column: 5 })
+        );
+        assert_eq!(
+            parse("    [B]\n[A] ?\n 1   2"),
+            Err(DrawingError::Stray { line: 2, column: 5 })
+        );
+        assert_eq!(
+            parse("[] [B]\n 1   2"),
+            Err(DrawingError::Stray { line: 1, column: 1 })
+        );
+        assert_eq!(
+            rearrange("[A]\n", CrateMover9000).err(),
+            Some(CraneError::Drawing(DrawingError::NoFooter))
+        );
+    }
 }
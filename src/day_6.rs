@@ -73,39 +73,151 @@
 // How many characters need to be processed before the first start-of-message
 // marker is detected?
 
-use std::collections::HashSet;
+use std::io::{self, BufReader, Bytes, Read};
 
-pub fn part_1(input: &str) -> Vec<usize> {
-    let mut outputs = vec![];
-    for line in input.lines() {
-        for (idx, c) in line.as_bytes().windows(4).enumerate() {
-            let set = c.iter().copied().collect::<HashSet<_>>();
-            if set.len() == c.len() {
-                outputs.push(idx + c.len());
-                break;
+/// Watches a stream one byte at a time for markers: places where the last
+/// `window` bytes are all different. Each byte costs O(1) however big the
+/// window is, since we only track how many values are currently repeated.
+#[derive(Debug, Clone)]
+pub struct Detector {
+    window: usize,
+    counts: [usize; 256],
+    /// The last `window` bytes, indexed by position modulo `window`.
+    recent: Vec<u8>,
+    /// How many byte values appear more than once in the window.
+    repeated: usize,
+    seen: usize,
+}
+
+impl Detector {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "a marker needs at least one byte");
+        Detector {
+            window,
+            counts: [0; 256],
+            recent: vec![0; window],
+            repeated: 0,
+            seen: 0,
+        }
+    }
+
+    /// How many bytes have been pushed so far.
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// Adds the next byte, returning whether a marker ends with it.
+    pub fn push(&mut self, byte: u8) -> bool {
+        let slot = self.seen % self.window;
+        if self.seen >= self.window {
+            let old = self.recent[slot] as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] == 1 {
+                self.repeated -= 1;
             }
         }
+        self.recent[slot] = byte;
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.repeated += 1;
+        }
+        self.seen += 1;
+        self.seen >= self.window && self.repeated == 0
+    }
+
+    /// Starts again as if nothing had been pushed.
+    pub fn reset(&mut self) {
+        *self = Detector::new(self.window);
     }
-    outputs
 }
 
-pub fn part_2(input: &str) -> Vec<usize> {
-    let mut outputs = vec![];
-    for line in input.lines() {
-        for (idx, c) in line.as_bytes().windows(14).enumerate() {
-            let set = c.iter().copied().collect::<HashSet<_>>();
-            if set.len() == c.len() {
-                outputs.push(idx + c.len());
-                break;
+/// The position after every marker in a stream of bytes, counting from 1 as
+/// the puzzle does.
+pub struct Markers<I> {
+    bytes: I,
+    detector: Detector,
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Markers<I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        for byte in self.bytes.by_ref() {
+            if self.detector.push(byte) {
+                return Some(self.detector.seen());
             }
         }
+        None
+    }
+}
+
+pub fn markers<I: IntoIterator<Item = u8>>(bytes: I, window: usize) -> Markers<I::IntoIter> {
+    Markers {
+        bytes: bytes.into_iter(),
+        detector: Detector::new(window),
+    }
+}
+
+/// Like `Markers`, but reading from anything that implements `Read`, a
+/// buffer at a time, so streams of any size can be scanned. Stops after the
+/// first read error.
+pub struct ReadMarkers<R> {
+    bytes: Bytes<BufReader<R>>,
+    detector: Detector,
+    failed: bool,
+}
+
+impl<R: Read> Iterator for ReadMarkers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<io::Result<usize>> {
+        if self.failed {
+            return None;
+        }
+        for byte in self.bytes.by_ref() {
+            match byte {
+                Ok(byte) if self.detector.push(byte) => return Some(Ok(self.detector.seen())),
+                Ok(_) => (),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn read_markers<R: Read>(reader: R, window: usize) -> ReadMarkers<R> {
+    ReadMarkers {
+        bytes: BufReader::new(reader).bytes(),
+        detector: Detector::new(window),
+        failed: false,
     }
-    outputs
+}
+
+/// The first marker on each line, or `None` if a line has none.
+pub fn first_markers(input: &str, window: usize) -> Vec<Option<usize>> {
+    input
+        .lines()
+        .map(|line| markers(line.bytes(), window).next())
+        .collect()
+}
+
+pub fn part_1(input: &str) -> Vec<usize> {
+    first_markers(input, 4).into_iter().flatten().collect()
+}
+
+pub fn part_2(input: &str) -> Vec<usize> {
+    first_markers(input, 14).into_iter().flatten().collect()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_6::{part_1, part_2};
+    use std::collections::HashSet;
+    use std::io::{self, Read};
+
+    use crate::day_6::{first_markers, markers, part_1, part_2, read_markers, Detector};
 
     const INPUTS: &str = r#"mjqjpqmgbljsphdztnvjfqwrcgsmlb
 bvwbjplbgvbhsrlpgdmjqwftvncz
@@ -132,4 +244,87 @@ zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"#;
     pub fn test_day_6_part2() {
         assert_eq!(part_2(include_str!("input/day_6.txt")), vec![3605]);
     }
+
+    // Every marker, checking each window from scratch.
+    fn brute_force(bytes: &[u8], window: usize) -> Vec<usize> {
+        bytes
+            .windows(window)
+            .enumerate()
+            .filter(|(_, w)| w.iter().collect::<HashSet<_>>().len() == window)
+            .map(|(idx, _)| idx + window)
+            .collect()
+    }
+
+    #[test]
+    pub fn test_day_6_all_markers() {
+        let input = include_str!("input/day_6.txt").trim().as_bytes();
+        for window in [1, 2, 4, 7, 14, 20] {
+            let expected = brute_force(input, window);
+            assert_eq!(
+                markers(input.iter().copied(), window).collect::<Vec<_>>(),
+                expected
+            );
+        }
+        assert_eq!(markers(*b"abcdd", 4).collect::<Vec<_>>(), vec![4]);
+        assert_eq!(markers(*b"aaaa", 1).count(), 4);
+        assert_eq!(markers(*b"abc", 4).next(), None);
+        assert_eq!(
+            first_markers("abcd\naab\n\nxyzzyxw", 4),
+            vec![Some(4), None, None, Some(7)]
+        );
+
+        let mut detector = Detector::new(3);
+        assert!(!detector.push(b'a') && !detector.push(b'b') && detector.push(b'c'));
+        detector.reset();
+        assert!(!detector.push(b'c') && detector.seen() == 1);
+    }
+
+    // An endless stream of the alphabet with a repeat every so often, so
+    // nothing but the reader ever holds more than a buffer of it.
+    struct Alphabet {
+        sent: usize,
+        limit: usize,
+    }
+
+    impl Read for Alphabet {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.limit - self.sent);
+            for (idx, b) in buf[..n].iter_mut().enumerate() {
+                let pos = self.sent + idx;
+                *b = if pos % 1000 == 999 {
+                    b'a'
+                } else {
+                    b'a' + (pos % 26) as u8
+                };
+            }
+            self.sent += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    pub fn test_day_6_read_markers() {
+        let input = include_str!("input/day_6.txt");
+        let found = read_markers(input.as_bytes(), 14).next().unwrap().unwrap();
+        assert_eq!(found, 3605);
+
+        let limit = 1 << 20;
+        let stream = Alphabet { sent: 0, limit };
+        let found = read_markers(stream, 26)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let mut bytes = vec![];
+        Alphabet { sent: 0, limit }.read_to_end(&mut bytes).unwrap();
+        assert_eq!(found, brute_force(&bytes, 26));
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("unplugged"))
+            }
+        }
+        let mut results = read_markers(Broken, 4);
+        assert!(results.next().unwrap().is_err());
+        assert!(results.next().is_none());
+    }
 }
@@ -143,8 +143,8 @@
 // Find the smallest directory that, if deleted, would free up enough space on
 // the filesystem to run the update. What is the total size of that directory?
 
-use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
 fn proc(input: &str) -> BTreeMap<PathBuf, (bool, usize)> {
     let mut path_info: BTreeMap<PathBuf, (bool, usize)> = BTreeMap::new();
@@ -197,7 +197,7 @@ fn proc(input: &str) -> BTreeMap<PathBuf, (bool, usize)> {
                     _ => unimplemented!("unrecognized command {:?}", cmd),
                 }
             }
-            Some(s) if s.is_empty() => continue,
+            Some("") => continue,
             None => break,
             _ => unreachable!(),
         }
@@ -217,6 +217,360 @@ fn proc(input: &str) -> BTreeMap<PathBuf, (bool, usize)> {
     path_info
 }
 
+/// Something in a transcript that `proc` would silently paper over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The same file was listed with two different sizes.
+    ConflictingSize {
+        path: String,
+        first: usize,
+        second: usize,
+    },
+    /// A directory whose contents were never listed, so its size is unknown.
+    NeverListed {
+        path: String,
+    },
+    /// `cd` into a directory missing from its parent's listing.
+    NotInListing {
+        path: String,
+    },
+    /// `cd ..` while already at the root.
+    AboveRoot,
+    /// The same name used for both a file and a directory.
+    NameCollision {
+        path: String,
+    },
+    UnknownCommand(String),
+    Malformed(String),
+}
+
+/// An issue and the line it was found on, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub line: usize,
+    pub kind: IssueKind,
+}
+
+fn display(path: &Path) -> String {
+    format!("/{}", path.display())
+}
+
+/// Checks that a transcript describes a single consistent filesystem,
+/// returning every problem found in line order.
+pub fn validate(input: &str) -> Vec<Issue> {
+    // Where each path was first seen, and its size if it's a file.
+    let mut seen: BTreeMap<PathBuf, (usize, Option<usize>)> = BTreeMap::new();
+    let mut listed: HashSet<PathBuf> = HashSet::new();
+    let mut issues = vec![];
+    let mut cwd = PathBuf::new();
+    let mut listing = false;
+    seen.insert(cwd.clone(), (1, None));
+
+    for (idx, line) in input.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut report = |kind| {
+            issues.push(Issue {
+                line: line_no,
+                kind,
+            })
+        };
+        let words = line.split_ascii_whitespace().collect::<Vec<_>>();
+
+        match words[..] {
+            [] => continue,
+            ["$", "cd", target] => {
+                listing = false;
+                match target {
+                    "/" => cwd = PathBuf::new(),
+                    "." => (),
+                    ".." => {
+                        if !cwd.pop() {
+                            report(IssueKind::AboveRoot);
+                        }
+                    }
+                    name => {
+                        let parent = cwd.clone();
+                        cwd.push(name);
+                        match seen.get(&cwd) {
+                            Some((_, Some(_))) => report(IssueKind::NameCollision {
+                                path: display(&cwd),
+                            }),
+                            Some((_, None)) => (),
+                            None => {
+                                if listed.contains(&parent) {
+                                    report(IssueKind::NotInListing {
+                                        path: display(&cwd),
+                                    });
+                                }
+                                seen.insert(cwd.clone(), (line_no, None));
+                            }
+                        }
+                    }
+                }
+            }
+            ["$", "ls"] => {
+                listing = true;
+                listed.insert(cwd.clone());
+            }
+            ["$", ..] => {
+                listing = false;
+                report(IssueKind::UnknownCommand(line.to_string()));
+            }
+            [kind, name] if listing => {
+                let size = match kind {
+                    "dir" => None,
+                    v => match v.parse::<usize>() {
+                        Ok(size) => Some(size),
+                        Err(_) => {
+                            report(IssueKind::Malformed(line.to_string()));
+                            continue;
+                        }
+                    },
+                };
+                let path = cwd.join(name);
+                match (seen.get(&path), size) {
+                    (None, _) => {
+                        seen.insert(path, (line_no, size));
+                    }
+                    (Some((_, Some(first))), Some(second)) if *first != second => {
+                        report(IssueKind::ConflictingSize {
+                            path: display(&path),
+                            first: *first,
+                            second,
+                        })
+                    }
+                    (Some((_, a)), b) if a.is_some() != b.is_some() => {
+                        report(IssueKind::NameCollision {
+                            path: display(&path),
+                        })
+                    }
+                    _ => (),
+                }
+            }
+            _ => report(IssueKind::Malformed(line.to_string())),
+        }
+    }
+
+    for (path, (line, size)) in seen {
+        if size.is_none() && !listed.contains(&path) {
+            issues.push(Issue {
+                line,
+                kind: IssueKind::NeverListed {
+                    path: display(&path),
+                },
+            });
+        }
+    }
+
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+/// Like [`part_1`], but refuses to answer for an inconsistent transcript.
+pub fn part_1_checked(input: &str) -> Result<usize, Vec<Issue>> {
+    match validate(input) {
+        issues if issues.is_empty() => Ok(part_1(input)),
+        issues => Err(issues),
+    }
+}
+
+/// Like [`part_2`], but refuses to answer for an inconsistent transcript.
+pub fn part_2_checked(input: &str) -> Result<usize, Vec<Issue>> {
+    match validate(input) {
+        issues if issues.is_empty() => Ok(part_2(input)),
+        issues => Err(issues),
+    }
+}
+
+/// The lowest set bit at or after `from`.
+fn first_set_from(bits: &[u64], from: usize) -> Option<usize> {
+    let mut idx = from / 64;
+    let mut word = *bits.get(idx)? & (u64::MAX << (from % 64));
+    while word == 0 {
+        idx += 1;
+        word = *bits.get(idx)?;
+    }
+    Some(idx * 64 + word.trailing_zeros() as usize)
+}
+
+/// Directories to delete, and how much space that frees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deletion {
+    pub paths: Vec<String>,
+    pub freed: usize,
+}
+
+/// The most [`Planner::multiple`] will try to free. Its table takes four
+/// bytes per byte to free, so this caps it at 128 MiB, which still covers the
+/// puzzle's disk.
+pub const MAX_MULTIPLE: usize = 1 << 25;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanError {
+    /// More needs freeing than [`MAX_MULTIPLE`]; [`Planner::single`] still
+    /// works at any size.
+    TooLarge { needed: usize },
+}
+
+/// Works out what to delete to make room on a disk.
+pub struct Planner {
+    path_info: BTreeMap<PathBuf, (bool, usize)>,
+    disk_size: usize,
+    required: usize,
+}
+
+impl Planner {
+    pub fn new(input: &str, disk_size: usize, required: usize) -> Self {
+        Planner {
+            path_info: proc(input),
+            disk_size,
+            required,
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.path_info[&PathBuf::new()].1
+    }
+
+    /// How much more space has to be freed, which may be nothing.
+    pub fn to_free(&self) -> usize {
+        self.required
+            .saturating_sub(self.disk_size.saturating_sub(self.used()))
+    }
+
+    fn dirs(&self) -> impl Iterator<Item = (&PathBuf, usize)> + '_ {
+        self.path_info
+            .iter()
+            .filter(|(_, v)| v.0)
+            .map(|(p, v)| (p, v.1))
+    }
+
+    /// The smallest single directory which frees enough space, or `None` if
+    /// even deleting everything isn't enough.
+    pub fn single(&self) -> Option<Deletion> {
+        let needed = self.to_free();
+        if needed == 0 {
+            return Some(Deletion {
+                paths: vec![],
+                freed: 0,
+            });
+        }
+        self.dirs()
+            .filter(|(_, size)| *size >= needed)
+            .min_by_key(|(_, size)| *size)
+            .map(|(path, size)| Deletion {
+                paths: vec![display(path)],
+                freed: size,
+            })
+    }
+
+    /// The set of directories, none inside another, freeing enough space
+    /// with the smallest total size.
+    ///
+    /// This is a subset-sum over the sizes below the amount needed, taking
+    /// directories children first. Deleting a directory excludes the ones
+    /// inside it, which are exactly the ones taken just before it, so the
+    /// sums reachable without them are whatever had been reached when its
+    /// subtree started. The first directory to reach each sum is remembered
+    /// to recover the paths. That takes memory in proportion to the amount
+    /// needed, so it's limited to [`MAX_MULTIPLE`].
+    pub fn multiple(&self) -> Result<Option<Deletion>, PlanError> {
+        let needed = self.to_free();
+        let Some(single) = self.single() else {
+            return Ok(None);
+        };
+        if needed == 0 {
+            return Ok(Some(single));
+        }
+        if needed > MAX_MULTIPLE {
+            return Err(PlanError::TooLarge { needed });
+        }
+
+        // Anything at least as big as the amount needed is best deleted on
+        // its own, so only smaller directories take part. Reversing the
+        // sorted paths puts every directory right after its subdirectories.
+        let items = self
+            .dirs()
+            .filter(|(_, size)| (1..needed).contains(size))
+            .collect::<Vec<_>>();
+        let items = items.into_iter().rev().collect::<Vec<_>>();
+        let starts = (0..items.len())
+            .map(|k| {
+                let mut start = k;
+                while start > 0 && items[start - 1].0.starts_with(items[k].0) {
+                    start -= 1;
+                }
+                start
+            })
+            .collect::<Vec<_>>();
+
+        const UNREACHED: u32 = u32::MAX;
+        let words = needed.div_ceil(64);
+        let mut reached_by = vec![UNREACHED; needed];
+        let mut reached = vec![0u64; words];
+        reached[0] = 1;
+        let mut snapshots: BTreeMap<usize, Vec<u64>> = BTreeMap::new();
+        // Best way over the line: total, last directory, and the sum it was
+        // added to.
+        let mut best: Option<(usize, usize, usize)> = None;
+
+        for (k, (_, size)) in items.iter().enumerate() {
+            if starts[k..].contains(&k) && !snapshots.contains_key(&k) {
+                snapshots.insert(k, reached.clone());
+            }
+            let before = &snapshots[&starts[k]];
+
+            // Smallest earlier sum which this directory pushes over the line.
+            let over = first_set_from(before, needed - size);
+            if let Some(x) = over {
+                if best.is_none_or(|b| x + size < b.0) {
+                    best = Some((x + size, k, x));
+                }
+            }
+
+            let (shift, bits) = (size / 64, size % 64);
+            for i in (shift..words).rev() {
+                let mut word = before[i - shift] << bits;
+                if bits > 0 && i > shift {
+                    word |= before[i - shift - 1] >> (64 - bits);
+                }
+                if i == words - 1 && !needed.is_multiple_of(64) {
+                    word &= (1 << (needed % 64)) - 1;
+                }
+                let mut new = word & !reached[i];
+                reached[i] |= new;
+                while new != 0 {
+                    let bit = new.trailing_zeros() as usize;
+                    reached_by[i * 64 + bit] = k as u32;
+                    new &= new - 1;
+                }
+            }
+
+            // Done with snapshots no later directory starts from.
+            if !starts[k + 1..].contains(&starts[k]) {
+                snapshots.remove(&starts[k]);
+            }
+        }
+
+        match best {
+            Some((total, last, mut x)) if total < single.freed => {
+                let mut paths = vec![display(items[last].0)];
+                while x > 0 {
+                    let k = reached_by[x] as usize;
+                    paths.push(display(items[k].0));
+                    x -= items[k].1;
+                }
+                paths.sort();
+                Ok(Some(Deletion {
+                    paths,
+                    freed: total,
+                }))
+            }
+            _ => Ok(Some(single)),
+        }
+    }
+}
+
 pub fn part_1(input: &str) -> usize {
     let path_info = proc(input);
     path_info
@@ -227,24 +581,18 @@ pub fn part_1(input: &str) -> usize {
 }
 
 pub fn part_2(input: &str) -> usize {
-    let path_info = proc(input);
-
-    let used_space = path_info[&PathBuf::new()].1;
-    let free_space = 70000000 - used_space;
-    let needed_space = 30000000 - free_space;
-
-    let mut options = path_info
-        .values()
-        .filter(|v| v.0 && v.1 >= needed_space)
-        .map(|v| v.1)
-        .collect::<Vec<_>>();
-    options.sort();
-    options[0]
+    Planner::new(input, 70000000, 30000000)
+        .single()
+        .unwrap()
+        .freed
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_7::{part_1, part_2};
+    use crate::day_7::{
+        part_1, part_1_checked, part_2, part_2_checked, validate, Deletion, Issue, IssueKind,
+        PlanError, Planner,
+    };
 
     const INPUTS: &str = r#"$ cd /
 $ ls
@@ -289,4 +637,153 @@ $ ls
     pub fn test_day_7_part2() {
         assert_eq!(part_2(include_str!("input/day_7.txt")), 6999588);
     }
+
+    #[test]
+    pub fn test_day_7_validate_clean() {
+        assert_eq!(validate(INPUTS), vec![]);
+        assert_eq!(part_1_checked(INPUTS), Ok(95437));
+        assert_eq!(part_2_checked(INPUTS), Ok(24933642));
+        assert_eq!(validate(include_str!("input/day_7.txt")), vec![]);
+    }
+
+    #[test]
+    pub fn test_day_7_validate_issues() {
+        let transcript = r#"$ cd /
+$ ls
+dir a
+10 b
+dir c
+$ cd ..
+$ cd a
+$ ls
+5 x
+6 x
+dir x
+$ cd ..
+$ cd b
+$ cd /
+$ cd z
+$ rm -rf /
+removed everything
+$ ls
+12 q
+$ cd /
+$ ls
+bogus line"#;
+        let issues = validate(transcript);
+        let at = |line, kind| Issue { line, kind };
+        assert_eq!(
+            issues,
+            vec![
+                at(5, IssueKind::NeverListed { path: "/c".into() }),
+                at(6, IssueKind::AboveRoot),
+                at(
+                    10,
+                    IssueKind::ConflictingSize {
+                        path: "/a/x".into(),
+                        first: 5,
+                        second: 6
+                    }
+                ),
+                at(
+                    11,
+                    IssueKind::NameCollision {
+                        path: "/a/x".into()
+                    }
+                ),
+                at(13, IssueKind::NameCollision { path: "/b".into() }),
+                at(15, IssueKind::NotInListing { path: "/z".into() }),
+                at(16, IssueKind::UnknownCommand("$ rm -rf /".into())),
+                at(17, IssueKind::Malformed("removed everything".into())),
+                at(22, IssueKind::Malformed("bogus line".into())),
+            ]
+        );
+        assert_eq!(part_1_checked(transcript), Err(issues));
+    }
+
+    const NESTED: &str = r#"$ cd /
+$ ls
+dir p
+dir r
+dir s
+$ cd p
+$ ls
+10 f
+dir q
+$ cd q
+$ ls
+9 g
+$ cd /
+$ cd r
+$ ls
+12 h
+$ cd /
+$ cd s
+$ ls
+3 i"#;
+
+    fn deletion(paths: &[&str], freed: usize) -> Option<Deletion> {
+        Some(Deletion {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            freed,
+        })
+    }
+
+    #[test]
+    pub fn test_day_7_planner_example() {
+        let planner = Planner::new(INPUTS, 70000000, 30000000);
+        assert_eq!(planner.to_free(), 8381165);
+        assert_eq!(planner.single(), deletion(&["/d"], 24933642));
+        assert_eq!(planner.multiple(), Ok(deletion(&["/d"], 24933642)));
+
+        let planner = Planner::new(INPUTS, 70000000, 21618835);
+        assert_eq!(planner.to_free(), 0);
+        assert_eq!(planner.multiple(), Ok(deletion(&[], 0)));
+    }
+
+    #[test]
+    pub fn test_day_7_planner_multiple() {
+        // /p and /p/q would free exactly 28, but deleting /p already
+        // deletes /p/q.
+        let planner = Planner::new(NESTED, 40, 34);
+        assert_eq!(planner.to_free(), 28);
+        assert_eq!(planner.single(), deletion(&["/"], 34));
+        assert_eq!(planner.multiple(), Ok(deletion(&["/p", "/r"], 31)));
+
+        let planner = Planner::new(NESTED, 40, 30);
+        assert_eq!(planner.multiple(), Ok(deletion(&["/p/q", "/r", "/s"], 24)));
+
+        let planner = Planner::new(NESTED, 40, 50);
+        assert_eq!(planner.single(), None);
+        assert_eq!(planner.multiple(), Ok(None));
+
+        let huge = "$ cd /\n$ ls\n40000000 big";
+        let planner = Planner::new(huge, 40000000, 40000000);
+        assert_eq!(
+            planner.multiple(),
+            Err(PlanError::TooLarge { needed: 40000000 })
+        );
+        assert_eq!(planner.single(), deletion(&["/"], 40000000));
+    }
+
+    #[test]
+    pub fn test_day_7_planner_real_input() {
+        let input = include_str!("input/day_7.txt");
+        let planner = Planner::new(input, 70000000, 30000000);
+        let single = planner.single().unwrap();
+        let multiple = planner.multiple().unwrap().unwrap();
+        assert_eq!(single.freed, 6999588);
+        assert!(multiple.freed >= planner.to_free());
+        assert!(multiple.freed <= single.freed);
+
+        let sizes = crate::day_7::proc(input);
+        let mut total = 0;
+        for (idx, a) in multiple.paths.iter().enumerate() {
+            for b in &multiple.paths[idx + 1..] {
+                assert!(!b.starts_with(&format!("{}/", a)));
+            }
+            total += sizes[std::path::Path::new(&a[1..])].1;
+        }
+        assert_eq!(total, multiple.freed);
+    }
 }
@@ -0,0 +1,333 @@
+// Differential tests for the day 7 solvers: random filesystems are turned into
+// valid transcripts, and every implementation has to agree on the answers.
+
+use std::panic;
+
+use crate::{day_7, day_7_tree, day_7_tree_2};
+
+/// xorshift64*, so failures can be reproduced from the seed alone.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero.
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    File(usize),
+    Dir(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn size(&self) -> usize {
+        match self {
+            Node::File(size) => *size,
+            Node::Dir(children) => children.iter().map(|(_, c)| c.size()).sum(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Node::File(_) => 1,
+            Node::Dir(children) => 1 + children.iter().map(|(_, c)| c.count()).sum::<usize>(),
+        }
+    }
+}
+
+fn random_name(rng: &mut Rng, taken: &[(String, Node)], file: bool) -> String {
+    loop {
+        let len = 1 + rng.below(4);
+        let mut name = (0..len)
+            .map(|_| (b'a' + rng.below(26) as u8) as char)
+            .collect::<String>();
+        if file && rng.chance(40) {
+            name.push_str([".txt", ".dat", ".lst", ".log"][rng.below(4)]);
+        }
+        if !taken.iter().any(|(n, _)| *n == name) {
+            return name;
+        }
+    }
+}
+
+fn random_dir(rng: &mut Rng, depth: usize, budget: &mut usize) -> Node {
+    let mut children = vec![];
+    let entries = rng.below(6);
+    for _ in 0..entries {
+        if *budget == 0 {
+            break;
+        }
+        *budget -= 1;
+        if depth < 6 && rng.chance(35) {
+            let name = random_name(rng, &children, false);
+            let dir = random_dir(rng, depth + 1, budget);
+            children.push((name, dir));
+        } else {
+            let name = random_name(rng, &children, true);
+            // Mostly small files, so some directories land under the part 1
+            // limit, with the odd huge one.
+            let size = if rng.chance(10) {
+                1 + rng.below(20_000_000)
+            } else {
+                1 + rng.below(60_000)
+            };
+            children.push((name, Node::File(size)));
+        }
+    }
+    Node::Dir(children)
+}
+
+/// A filesystem whose used space is enough for part 2 to have an answer.
+fn random_fs(rng: &mut Rng) -> Node {
+    let mut budget = 5 + rng.below(60);
+    let mut root = random_dir(rng, 0, &mut budget);
+    let used = root.size();
+    if !(40_000_001..=70_000_000).contains(&used) {
+        if let Node::Dir(children) = &mut root {
+            // Smallest first, so dropping from the end brings us under the
+            // disk size.
+            children.sort_by_key(|(_, c)| c.size());
+            while root.size() > 70_000_000 {
+                if let Node::Dir(children) = &mut root {
+                    children.pop();
+                }
+            }
+            let used = root.size();
+            if used <= 40_000_000 {
+                if let Node::Dir(children) = &mut root {
+                    let name = random_name(rng, children, true);
+                    let pad = 40_000_001 - used + rng.below(20_000_000);
+                    children.push((name, Node::File(pad)));
+                }
+            }
+        }
+    }
+    root
+}
+
+/// A valid terminal session exploring the whole filesystem, wandering about
+/// the way a person might.
+fn transcript(rng: &mut Rng, root: &Node) -> String {
+    fn explore(rng: &mut Rng, node: &Node, path: &mut Vec<String>, out: &mut Vec<String>) {
+        let children = match node {
+            Node::Dir(children) => children,
+            Node::File(_) => return,
+        };
+        let listings = if rng.chance(10) { 2 } else { 1 };
+        for _ in 0..listings {
+            out.push("$ ls".to_string());
+            let mut entries = children.iter().collect::<Vec<_>>();
+            rng.shuffle(&mut entries);
+            for (name, child) in entries {
+                out.push(match child {
+                    Node::File(size) => format!("{} {}", size, name),
+                    Node::Dir(_) => format!("dir {}", name),
+                });
+            }
+        }
+
+        let mut dirs = children
+            .iter()
+            .filter(|(_, c)| matches!(c, Node::Dir(_)))
+            .collect::<Vec<_>>();
+        rng.shuffle(&mut dirs);
+        for (name, child) in dirs {
+            out.push(format!("$ cd {}", name));
+            path.push(name.clone());
+            explore(rng, child, path, out);
+            path.pop();
+
+            // Come back either one step at a time or from the top.
+            if rng.chance(75) {
+                out.push("$ cd ..".to_string());
+            } else {
+                out.push("$ cd /".to_string());
+                for segment in path.iter() {
+                    out.push(format!("$ cd {}", segment));
+                }
+            }
+        }
+    }
+
+    let mut out = vec!["$ cd /".to_string()];
+    explore(rng, root, &mut vec![], &mut out);
+    out.join("\n")
+}
+
+type Answers = Vec<(Option<usize>, Option<usize>)>;
+type Part = fn(&str) -> usize;
+
+/// Each solver's answers, with `None` for a panic.
+fn answers(input: &str) -> Answers {
+    let solvers: [(Part, Part); 3] = [
+        (day_7::part_1, day_7::part_2),
+        (day_7_tree::part_1, day_7_tree::part_2),
+        (day_7_tree_2::part_1, day_7_tree_2::part_2),
+    ];
+    solvers
+        .iter()
+        .map(|(p1, p2)| {
+            (
+                panic::catch_unwind(|| p1(input)).ok(),
+                panic::catch_unwind(|| p2(input)).ok(),
+            )
+        })
+        .collect()
+}
+
+fn agree(answers: &Answers) -> bool {
+    answers.windows(2).all(|w| w[0] == w[1])
+}
+
+/// Smaller filesystems to try in place of `node`: each entry removed, each
+/// file halved, and each subdirectory shrunk in turn.
+fn shrinks(node: &Node) -> Vec<Node> {
+    let children = match node {
+        Node::File(size) if *size > 1 => return vec![Node::File(size / 2)],
+        Node::File(_) => return vec![],
+        Node::Dir(children) => children,
+    };
+    let mut out = vec![];
+    for idx in 0..children.len() {
+        let mut fewer = children.clone();
+        fewer.remove(idx);
+        out.push(Node::Dir(fewer));
+    }
+    for (idx, (name, child)) in children.iter().enumerate() {
+        for smaller in shrinks(child) {
+            let mut changed = children.clone();
+            changed[idx] = (name.clone(), smaller);
+            out.push(Node::Dir(changed));
+        }
+    }
+    out
+}
+
+/// Greedily shrinks a filesystem for as long as it keeps failing.
+fn shrink(mut fs: Node, fails: impl Fn(&Node) -> bool) -> Node {
+    'outer: loop {
+        for candidate in shrinks(&fs) {
+            if fails(&candidate) {
+                fs = candidate;
+                continue 'outer;
+            }
+        }
+        return fs;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::day_7::Planner;
+    use crate::day_7_diff::{agree, answers, random_dir, random_fs, shrink, transcript, Node, Rng};
+
+    // Every directory's path and total size.
+    fn dirs(node: &Node, path: String, out: &mut Vec<(String, usize)>) {
+        if let Node::Dir(children) = node {
+            out.push((path.clone(), node.size()));
+            for (name, child) in children {
+                dirs(child, format!("{}/{}", path, name), out);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_7_solvers_agree() {
+        for seed in 0..300 {
+            let render = |fs: &Node| transcript(&mut Rng::new(seed), fs);
+            let fs = random_fs(&mut Rng::new(seed));
+            let results = answers(&render(&fs));
+            assert!(results[0].0.is_some() && results[0].1.is_some());
+
+            if !agree(&results) {
+                let minimal = render(&shrink(fs, |c| !agree(&answers(&render(c)))));
+                panic!(
+                    "seed {} disagrees: {:?}\nminimal transcript:\n{}\ngives {:?}",
+                    seed,
+                    results,
+                    minimal,
+                    answers(&minimal)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_7_shrinking_finds_small_cases() {
+        let fs = Node::Dir(vec![
+            ("a".into(), Node::Dir(vec![("x".into(), Node::File(5000))])),
+            ("b".into(), Node::File(10)),
+        ]);
+        // Pretend anything over 1000 bytes trips a bug.
+        let shrunk = shrink(fs, |c| c.size() > 1000);
+        assert_eq!(shrunk.count(), 3);
+        assert_eq!(shrunk.size(), 1250);
+    }
+
+    #[test]
+    pub fn test_day_7_planner_matches_brute_force() {
+        for seed in 0..200 {
+            let mut rng = Rng::new(seed);
+            let fs = random_dir(&mut rng, 0, &mut 14);
+            let used = fs.size();
+            if used == 0 {
+                continue;
+            }
+            let needed = 1 + rng.below(used);
+            let planner = Planner::new(&transcript(&mut rng, &fs), used, needed);
+            assert_eq!(planner.to_free(), needed);
+
+            let mut all = vec![];
+            dirs(&fs, String::new(), &mut all);
+            let nested = |a: &str, b: &str| b.starts_with(&format!("{}/", a));
+            let best = (0..1u32 << all.len())
+                .filter(|mask| {
+                    let chosen = (0..all.len()).filter(|i| mask >> i & 1 == 1);
+                    let chosen = chosen.collect::<Vec<_>>();
+                    chosen.iter().all(|&a| {
+                        chosen
+                            .iter()
+                            .all(|&b| a == b || !nested(&all[a].0, &all[b].0))
+                    })
+                })
+                .map(|mask| {
+                    (0..all.len())
+                        .filter(|i| mask >> i & 1 == 1)
+                        .map(|i| all[i].1)
+                        .sum::<usize>()
+                })
+                .filter(|total| *total >= needed)
+                .min();
+
+            assert_eq!(
+                planner.multiple().unwrap().map(|d| d.freed),
+                best,
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
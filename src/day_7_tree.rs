@@ -135,7 +135,7 @@ fn proc(input: &str) -> FS {
                     _ => unimplemented!("unrecognized command {:?}", cmd),
                 }
             }
-            Some(s) if s.is_empty() => continue,
+            Some("") => continue,
             None => break,
             _ => unreachable!(),
         }
@@ -149,7 +149,7 @@ fn proc(input: &str) -> FS {
 pub fn part_1(input: &str) -> usize {
     let fs = proc(input);
     fs.iter_root()
-        .filter(|dirent| dirent.is_dir && dirent.size < 100000)
+        .filter(|dirent| dirent.is_dir && dirent.size <= 100000)
         .map(|dirent| dirent.size)
         .sum()
 }
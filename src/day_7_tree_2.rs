@@ -61,16 +61,20 @@ fn proc(input: &str) -> Rc<RefCell<DirEnt>> {
                             let mut l = lines.next().unwrap().split_ascii_whitespace();
                             match l.next().unwrap() {
                                 "dir" => {
-                                    let new_node = Rc::new(RefCell::new(DirEnt {
-                                        parent: Some(Rc::downgrade(&cwd)),
-                                        is_dir: true,
-                                        size: 0,
-                                        children: HashMap::new(),
-                                    }));
-                                    cwd.borrow_mut().children.insert(
-                                        l.next().unwrap().to_string(),
-                                        Rc::clone(&new_node),
-                                    );
+                                    // Keep what we already know if the
+                                    // directory is listed again.
+                                    let parent = Rc::downgrade(&cwd);
+                                    cwd.borrow_mut()
+                                        .children
+                                        .entry(l.next().unwrap().to_string())
+                                        .or_insert_with(|| {
+                                            Rc::new(RefCell::new(DirEnt {
+                                                parent: Some(parent),
+                                                is_dir: true,
+                                                size: 0,
+                                                children: HashMap::new(),
+                                            }))
+                                        });
                                 }
                                 v => {
                                     let new_node = Rc::new(RefCell::new(DirEnt {
@@ -90,7 +94,7 @@ fn proc(input: &str) -> Rc<RefCell<DirEnt>> {
                     _ => unimplemented!("unrecognized command {:?}", cmd),
                 }
             }
-            Some(s) if s.is_empty() => continue,
+            Some("") => continue,
             None => break,
             _ => unreachable!(),
         }
@@ -127,9 +131,9 @@ pub fn part_1(input: &str) -> usize {
         let fstat = n.borrow();
         stk.extend(fstat.children.values().map(Rc::clone));
 
-        if !fstat.is_dir {
+        if fstat.is_dir {
             let size = fstat.size;
-            if size < 100000 {
+            if size <= 100000 {
                 sum += size
             }
         }
@@ -152,9 +156,9 @@ pub fn part_2(input: &str) -> usize {
         let fstat = n.borrow();
         stk.extend(fstat.children.values().map(Rc::clone));
 
-        if !fstat.is_dir {
+        if fstat.is_dir {
             let size = fstat.size;
-            if size > needed_space {
+            if size >= needed_space {
                 options.push(size);
             }
         }
@@ -166,7 +170,7 @@ pub fn part_2(input: &str) -> usize {
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_7_tree::{part_1, part_2};
+    use crate::day_7_tree_2::{part_1, part_2};
 
     const INPUTS: &str = r#"$ cd /
 $ ls
@@ -0,0 +1,501 @@
+use std::fmt::Write;
+
+// Option 3: a tree of owned nodes, which makes moving and removing whole
+// subtrees easy. Children are kept in the order they were created, which is
+// also how the puzzle prints them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    File(usize),
+    Dir(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn size(&self) -> usize {
+        match self {
+            Node::File(size) => *size,
+            Node::Dir(children) => children.iter().map(|(_, n)| n.size()).sum(),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Node> {
+        match self {
+            Node::Dir(children) => children.iter().find(|(n, _)| n == name).map(|(_, c)| c),
+            Node::File(_) => None,
+        }
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Node> {
+        match self {
+            Node::Dir(children) => children.iter_mut().find(|(n, _)| n == name).map(|(_, c)| c),
+            Node::File(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+}
+
+/// One line of `ls` output. Directories report the total size of everything
+/// below them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub kind: Kind,
+    pub size: usize,
+}
+
+/// Paths in errors are absolute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsError {
+    NotFound(String),
+    NotADirectory(String),
+    AlreadyExists(String),
+    /// The root can't be removed or moved.
+    Root,
+    /// A directory can't be moved inside itself.
+    IntoItself(String),
+    /// A transcript line which isn't a command we know or `ls` output, by
+    /// line number starting from 1.
+    BadLine(usize),
+}
+
+fn display(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualFs {
+    root: Node,
+    cwd: Vec<String>,
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        VirtualFs::new()
+    }
+}
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        VirtualFs {
+            root: Node::Dir(vec![]),
+            cwd: vec![],
+        }
+    }
+
+    /// Rebuilds the filesystem from a terminal session. Like the other day 7
+    /// solvers, `cd` into a directory which was never listed creates it.
+    pub fn replay(input: &str) -> Result<Self, FsError> {
+        let mut fs = VirtualFs::new();
+        for (idx, line) in input.lines().enumerate() {
+            let bad = || FsError::BadLine(idx + 1);
+            let words = line.split_ascii_whitespace().collect::<Vec<_>>();
+            match words[..] {
+                [] => continue,
+                ["$", "cd", path] => {
+                    fs.mkdir(path)?;
+                    fs.cd(path)?;
+                }
+                ["$", "ls"] => (),
+                ["dir", name] if !name.contains('/') => {
+                    fs.mkdir(name)?;
+                }
+                [size, name] if !name.contains('/') => {
+                    fs.create_file(name, size.parse().map_err(|_| bad())?)?;
+                }
+                _ => return Err(bad()),
+            }
+        }
+        fs.cwd.clear();
+        Ok(fs)
+    }
+
+    /// Turns a path, absolute or relative to the current directory, into its
+    /// segments from the root. `..` at the root stays there, like a shell.
+    fn resolve(&self, path: &str) -> Vec<String> {
+        let mut out = if path.starts_with('/') {
+            vec![]
+        } else {
+            self.cwd.clone()
+        };
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => (),
+                ".." => {
+                    out.pop();
+                }
+                s => out.push(s.to_string()),
+            }
+        }
+        out
+    }
+
+    fn node(&self, path: &[String]) -> Result<&Node, FsError> {
+        let mut node = &self.root;
+        for (idx, segment) in path.iter().enumerate() {
+            node = match node {
+                Node::Dir(_) => node
+                    .child(segment)
+                    .ok_or_else(|| FsError::NotFound(display(&path[..=idx])))?,
+                Node::File(_) => return Err(FsError::NotADirectory(display(&path[..idx]))),
+            };
+        }
+        Ok(node)
+    }
+
+    fn dir_mut(&mut self, path: &[String]) -> Result<&mut Vec<(String, Node)>, FsError> {
+        let mut node = &mut self.root;
+        for (idx, segment) in path.iter().enumerate() {
+            if let Node::File(_) = node {
+                return Err(FsError::NotADirectory(display(&path[..idx])));
+            }
+            node = node
+                .child_mut(segment)
+                .ok_or_else(|| FsError::NotFound(display(&path[..=idx])))?;
+        }
+        match node {
+            Node::Dir(children) => Ok(children),
+            Node::File(_) => Err(FsError::NotADirectory(display(path))),
+        }
+    }
+
+    /// The current directory, as an absolute path.
+    pub fn cwd(&self) -> String {
+        display(&self.cwd)
+    }
+
+    pub fn cd(&mut self, path: &str) -> Result<(), FsError> {
+        let target = self.resolve(path);
+        match self.node(&target)? {
+            Node::Dir(_) => {
+                self.cwd = target;
+                Ok(())
+            }
+            Node::File(_) => Err(FsError::NotADirectory(display(&target))),
+        }
+    }
+
+    pub fn ls(&self, path: &str) -> Result<Vec<Entry>, FsError> {
+        let target = self.resolve(path);
+        match self.node(&target)? {
+            Node::Dir(children) => Ok(children
+                .iter()
+                .map(|(name, node)| Entry {
+                    name: name.clone(),
+                    kind: match node {
+                        Node::File(_) => Kind::File,
+                        Node::Dir(_) => Kind::Dir,
+                    },
+                    size: node.size(),
+                })
+                .collect()),
+            Node::File(size) => Ok(vec![Entry {
+                name: target.last().cloned().unwrap_or_default(),
+                kind: Kind::File,
+                size: *size,
+            }]),
+        }
+    }
+
+    /// Creates a directory along with any missing parents. Succeeds if the
+    /// directory is already there.
+    pub fn mkdir(&mut self, path: &str) -> Result<(), FsError> {
+        let target = self.resolve(path);
+        for idx in 0..target.len() {
+            let children = self.dir_mut(&target[..idx])?;
+            match children.iter().find(|(n, _)| *n == target[idx]) {
+                Some((_, Node::Dir(_))) => (),
+                Some((_, Node::File(_))) => {
+                    return Err(FsError::NotADirectory(display(&target[..=idx])))
+                }
+                None => children.push((target[idx].clone(), Node::Dir(vec![]))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a file in an existing directory, or updates its size.
+    pub fn create_file(&mut self, path: &str, size: usize) -> Result<(), FsError> {
+        let target = self.resolve(path);
+        let (name, parent) = target.split_last().ok_or(FsError::Root)?;
+        let children = self.dir_mut(parent)?;
+        match children.iter_mut().find(|(n, _)| n == name) {
+            Some((_, Node::File(s))) => *s = size,
+            Some((_, Node::Dir(_))) => return Err(FsError::AlreadyExists(display(&target))),
+            None => children.push((name.clone(), Node::File(size))),
+        }
+        Ok(())
+    }
+
+    /// Takes a file or directory out of the tree.
+    fn detach(&mut self, target: &[String]) -> Result<(String, Node), FsError> {
+        let (name, parent) = target.split_last().ok_or(FsError::Root)?;
+        let children = self.dir_mut(parent)?;
+        let idx = children
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| FsError::NotFound(display(target)))?;
+        Ok(children.remove(idx))
+    }
+
+    /// Goes back to the root if the current directory no longer exists.
+    fn fix_cwd(&mut self) {
+        if !matches!(self.node(&self.cwd), Ok(Node::Dir(_))) {
+            self.cwd.clear();
+        }
+    }
+
+    /// Removes a file, or a directory and everything in it.
+    pub fn rm(&mut self, path: &str) -> Result<(), FsError> {
+        let target = self.resolve(path);
+        self.detach(&target)?;
+        self.fix_cwd();
+        Ok(())
+    }
+
+    /// Moves `from` into `to` if that is a directory, or renames it to `to`
+    /// otherwise.
+    pub fn mv(&mut self, from: &str, to: &str) -> Result<(), FsError> {
+        let source = self.resolve(from);
+        let name = source.last().ok_or(FsError::Root)?.clone();
+        self.node(&source)?;
+
+        let mut dest = self.resolve(to);
+        if let Ok(Node::Dir(_)) = self.node(&dest) {
+            dest.push(name);
+        }
+        if dest.starts_with(&source) {
+            return Err(FsError::IntoItself(display(&source)));
+        }
+        if self.node(&dest).is_ok() {
+            return Err(FsError::AlreadyExists(display(&dest)));
+        }
+
+        let (new_name, parent) = dest.split_last().ok_or(FsError::Root)?;
+        self.dir_mut(parent)?;
+        let (_, node) = self.detach(&source)?;
+        self.dir_mut(parent)?.push((new_name.clone(), node));
+        self.fix_cwd();
+        Ok(())
+    }
+
+    /// Total size of a file or directory.
+    pub fn du(&self, path: &str) -> Result<usize, FsError> {
+        Ok(self.node(&self.resolve(path))?.size())
+    }
+
+    /// Every file or directory of the given kind whose size matches, as
+    /// absolute paths in the order the tree prints them.
+    pub fn find(&self, kind: Kind, pred: impl Fn(usize) -> bool) -> Vec<(String, usize)> {
+        fn walk(
+            node: &Node,
+            path: &mut Vec<String>,
+            kind: Kind,
+            pred: &dyn Fn(usize) -> bool,
+            out: &mut Vec<(String, usize)>,
+        ) -> usize {
+            match node {
+                Node::File(size) => {
+                    if kind == Kind::File && pred(*size) {
+                        out.push((display(path), *size));
+                    }
+                    *size
+                }
+                Node::Dir(children) => {
+                    // Reserve our slot so parents come before their children.
+                    let slot = out.len();
+                    let mut size = 0;
+                    for (name, child) in children {
+                        path.push(name.clone());
+                        size += walk(child, path, kind, pred, out);
+                        path.pop();
+                    }
+                    if kind == Kind::Dir && pred(size) {
+                        out.insert(slot, (display(path), size));
+                    }
+                    size
+                }
+            }
+        }
+
+        let mut out = vec![];
+        walk(&self.root, &mut vec![], kind, &pred, &mut out);
+        out
+    }
+
+    /// The whole tree, drawn like the puzzle does.
+    pub fn tree(&self) -> String {
+        fn draw(name: &str, node: &Node, depth: usize, out: &mut String) {
+            let indent = "  ".repeat(depth);
+            match node {
+                Node::File(size) => {
+                    writeln!(out, "{}- {} (file, size={})", indent, name, size).unwrap()
+                }
+                Node::Dir(children) => {
+                    writeln!(out, "{}- {} (dir)", indent, name).unwrap();
+                    for (n, child) in children {
+                        draw(n, child, depth + 1, out);
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        draw("/", &self.root, 0, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::day_7_vfs::{Entry, FsError, Kind, VirtualFs};
+
+    const INPUTS: &str = r#"$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k"#;
+
+    const TREE: &str = r#"- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)
+"#;
+
+    #[test]
+    pub fn test_day_7_vfs_example() {
+        let fs = VirtualFs::replay(INPUTS).unwrap();
+        assert_eq!(fs.tree(), TREE);
+        assert_eq!(fs.du("/").unwrap(), 48381165);
+        assert_eq!(fs.du("a/e").unwrap(), 584);
+        assert_eq!(
+            fs.find(Kind::Dir, |s| s <= 100000),
+            vec![("/a".to_string(), 94853), ("/a/e".to_string(), 584)]
+        );
+        assert_eq!(
+            fs.find(Kind::File, |s| s > 8000000)
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect::<Vec<_>>(),
+            vec!["/b.txt", "/c.dat", "/d/d.log"]
+        );
+
+        let fs = VirtualFs::replay(include_str!("input/day_7.txt")).unwrap();
+        let small: usize = fs
+            .find(Kind::Dir, |s| s <= 100000)
+            .iter()
+            .map(|d| d.1)
+            .sum();
+        assert_eq!(small, 1642503);
+    }
+
+    #[test]
+    pub fn test_day_7_vfs_navigation() {
+        let mut fs = VirtualFs::replay(INPUTS).unwrap();
+        assert_eq!(fs.cwd(), "/");
+        fs.cd("a/e").unwrap();
+        assert_eq!(fs.cwd(), "/a/e");
+        fs.cd("../../d/./").unwrap();
+        assert_eq!(fs.cwd(), "/d");
+        fs.cd("/a").unwrap();
+        assert_eq!(fs.cwd(), "/a");
+        fs.cd("../../..").unwrap();
+        assert_eq!(fs.cwd(), "/");
+
+        assert_eq!(fs.cd("b.txt"), Err(FsError::NotADirectory("/b.txt".into())));
+        assert_eq!(fs.cd("a/x/y"), Err(FsError::NotFound("/a/x".into())));
+        assert_eq!(
+            fs.ls("b.txt/z"),
+            Err(FsError::NotADirectory("/b.txt".into()))
+        );
+
+        assert_eq!(
+            fs.ls("/a").unwrap()[..2],
+            [
+                Entry {
+                    name: "e".into(),
+                    kind: Kind::Dir,
+                    size: 584
+                },
+                Entry {
+                    name: "f".into(),
+                    kind: Kind::File,
+                    size: 29116
+                }
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_day_7_vfs_edits() {
+        let mut fs = VirtualFs::replay(INPUTS).unwrap();
+
+        fs.mkdir("/x/y/z").unwrap();
+        fs.mkdir("x/y").unwrap();
+        assert_eq!(
+            fs.mkdir("b.txt/q"),
+            Err(FsError::NotADirectory("/b.txt".into()))
+        );
+        fs.create_file("/x/y/z/big", 100).unwrap();
+        assert_eq!(
+            fs.create_file("/b.txt/big", 1),
+            Err(FsError::NotADirectory("/b.txt".into()))
+        );
+        assert_eq!(fs.du("x").unwrap(), 100);
+
+        fs.mv("/a/e", "/x/y").unwrap();
+        assert_eq!(fs.du("/x/y/e/i").unwrap(), 584);
+        assert_eq!(fs.du("/a").unwrap(), 94853 - 584);
+        fs.mv("/x/y/e", "/x/renamed").unwrap();
+        assert_eq!(fs.du("/x").unwrap(), 684);
+        assert_eq!(fs.mv("/x", "/x/y"), Err(FsError::IntoItself("/x".into())));
+        assert_eq!(
+            fs.mv("/b.txt", "/c.dat"),
+            Err(FsError::AlreadyExists("/c.dat".into()))
+        );
+        assert_eq!(fs.mv("/", "/a"), Err(FsError::Root));
+
+        fs.cd("/x/y/z").unwrap();
+        fs.rm("/x/y").unwrap();
+        assert_eq!(fs.cwd(), "/");
+        assert_eq!(fs.du("/x").unwrap(), 584);
+        assert_eq!(fs.rm("/x/y"), Err(FsError::NotFound("/x/y".into())));
+        assert_eq!(fs.rm("/"), Err(FsError::Root));
+
+        assert_eq!(
+            VirtualFs::replay("$ cd /\n$ frobnicate"),
+            Err(FsError::BadLine(2))
+        );
+    }
+}
@@ -113,108 +113,164 @@
 // Consider each tree on your map. What is the highest scenic score possible for
 // any tree?
 
-use std::collections::HashSet;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForestError {
+    /// `line` counts from 1, skipping nothing.
+    BadHeight { line: usize, token: String },
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
 
-#[allow(clippy::needless_range_loop)]
-pub fn part_1(input: &str) -> usize {
-    let grid = input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| {
-            l.chars()
-                .map(|c| (c as isize - '0' as isize))
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+/// A grid of tree heights, which doesn't have to be square.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forest {
+    width: usize,
+    height: usize,
+    /// Row by row, from the top left.
+    trees: Vec<u32>,
+}
 
-    let mut visible = HashSet::new();
+/// Per-tree results, indexed `[row][col]` like the map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub visible: Vec<Vec<bool>>,
+    pub scenic: Vec<Vec<usize>>,
+}
 
-    for i in 0..grid.len() {
-        let mut v = -1;
-        for j in 0..grid[i].len() {
-            if grid[i][j] > v {
-                visible.insert((i, j));
-                v = grid[i][j]
-            }
-        }
+impl Analysis {
+    pub fn visible_count(&self) -> usize {
+        self.visible.iter().flatten().filter(|v| **v).count()
     }
 
-    for i in 0..grid.len() {
-        let mut v = -1;
-        for j in (0..grid[i].len()).rev() {
-            if grid[i][j] > v {
-                visible.insert((i, j));
-                v = grid[i][j]
-            }
-        }
+    pub fn best_scenic(&self) -> usize {
+        self.scenic.iter().flatten().copied().max().unwrap_or(0)
     }
+}
 
-    for j in 0..grid[0].len() {
-        let mut v = -1;
-        for i in 0..grid.len() {
-            if grid[i][j] > v {
-                visible.insert((i, j));
-                v = grid[i][j]
-            }
+/// For each tree in `line`, looking towards the start: whether it can be
+/// seen from that edge, and how many trees it can see.
+///
+/// The stack holds the trees that could still block the view of a later
+/// tree, tallest at the bottom. Each tree is pushed and popped at most once.
+fn sweep(line: impl Iterator<Item = u32>) -> Vec<(bool, usize)> {
+    let mut stack: Vec<(usize, u32)> = vec![];
+    let mut out = vec![];
+    for (idx, h) in line.enumerate() {
+        while stack.last().is_some_and(|(_, top)| *top < h) {
+            stack.pop();
         }
+        out.push(match stack.last() {
+            Some((blocker, _)) => (false, idx - blocker),
+            None => (true, idx),
+        });
+        stack.push((idx, h));
     }
+    out
+}
 
-    for j in 0..grid[0].len() {
-        let mut v = -1;
-        for i in (0..grid.len()).rev() {
-            if grid[i][j] > v {
-                visible.insert((i, j));
-                v = grid[i][j]
+impl Forest {
+    /// Rows of single digits as in the puzzle, or of whitespace- or
+    /// comma-separated numbers for taller trees. Blank lines are skipped.
+    pub fn parse(input: &str) -> Result<Forest, ForestError> {
+        let mut rows = vec![];
+        for (idx, line) in input.lines().enumerate() {
+            let bad = |token: &str| ForestError::BadHeight {
+                line: idx + 1,
+                token: token.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
             }
+            let row = if line.contains(|c: char| c.is_whitespace() || c == ',') {
+                line.split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.parse::<u32>().map_err(|_| bad(t)))
+                    .collect::<Result<Vec<_>, _>>()?
+            } else {
+                line.chars()
+                    .map(|c| c.to_digit(10).ok_or_else(|| bad(&c.to_string())))
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            rows.push((idx + 1, row));
         }
-    }
-
-    visible.len()
-}
-
-fn count_until_including(iter: impl Iterator<Item = bool>) -> usize {
-    let mut ct = 0;
 
-    for v in iter {
-        ct += 1;
-        if !v {
-            break;
+        let width = rows.first().map_or(0, |(_, r)| r.len());
+        for (line, row) in &rows {
+            if row.len() != width {
+                return Err(ForestError::Ragged {
+                    line: *line,
+                    expected: width,
+                    found: row.len(),
+                });
+            }
         }
+        Ok(Forest {
+            width,
+            height: rows.len(),
+            trees: rows.into_iter().flat_map(|(_, r)| r).collect(),
+        })
     }
-    ct
-}
 
-pub fn part_2(input: &str) -> usize {
-    let grid = input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| {
-            l.chars()
-                .map(|c| (c as isize - '0' as isize))
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-    let mut max_score = 0;
+    pub fn height(&self) -> usize {
+        self.height
+    }
 
-    for i in 0..grid.len() {
-        for j in 0..grid[i].len() {
-            let h = grid[i][j];
-            let s1 = count_until_including((i + 1..grid.len()).map(|ii| grid[ii][j] < h));
-            let s2 = count_until_including((0..i).rev().map(|ii| grid[ii][j] < h));
-            let s3 = count_until_including((j + 1..grid[i].len()).map(|jj| grid[i][jj] < h));
-            let s4 = count_until_including((0..j).rev().map(|jj| grid[i][jj] < h));
+    pub fn get(&self, row: usize, col: usize) -> Option<u32> {
+        (row < self.height && col < self.width).then(|| self.trees[row * self.width + col])
+    }
 
-            max_score = max_score.max(s1 * s2 * s3 * s4);
+    /// Visibility and scenic scores for every tree, in O(width * height).
+    pub fn analyze(&self) -> Analysis {
+        let (w, h) = (self.width, self.height);
+        let mut visible = vec![vec![false; w]; h];
+        let mut scenic = vec![vec![1; w]; h];
+        let mut record = |row: usize, col: usize, (seen, distance): (bool, usize)| {
+            visible[row][col] |= seen;
+            scenic[row][col] *= distance;
+        };
+
+        for row in 0..h {
+            let line = &self.trees[row * w..(row + 1) * w];
+            for (col, r) in sweep(line.iter().copied()).into_iter().enumerate() {
+                record(row, col, r);
+            }
+            for (col, r) in sweep(line.iter().rev().copied()).into_iter().enumerate() {
+                record(row, w - 1 - col, r);
+            }
         }
+        for col in 0..w {
+            let line = (0..h).map(|row| self.trees[row * w + col]);
+            for (row, r) in sweep(line.clone()).into_iter().enumerate() {
+                record(row, col, r);
+            }
+            for (row, r) in sweep(line.rev()).into_iter().enumerate() {
+                record(h - 1 - row, col, r);
+            }
+        }
+
+        Analysis { visible, scenic }
     }
+}
 
-    max_score
+pub fn part_1(input: &str) -> usize {
+    Forest::parse(input).unwrap().analyze().visible_count()
+}
+
+pub fn part_2(input: &str) -> usize {
+    Forest::parse(input).unwrap().analyze().best_scenic()
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_8::{part_1, part_2};
+    use crate::day_8::{part_1, part_2, Forest, ForestError};
 
     const INPUTS: &str = r#"30373
 25512
@@ -241,4 +297,115 @@ pub mod tests {
     pub fn test_day_8_part2() {
         assert_eq!(part_2(include_str!("input/day_8.txt")), 263670);
     }
+
+    // Walks out from every tree in every direction.
+    fn brute_force(grid: &[Vec<u32>]) -> (Vec<Vec<bool>>, Vec<Vec<usize>>) {
+        let (h, w) = (grid.len() as isize, grid[0].len() as isize);
+        let mut visible = vec![vec![false; w as usize]; h as usize];
+        let mut scenic = vec![vec![1; w as usize]; h as usize];
+        for r in 0..h {
+            for c in 0..w {
+                let tree = grid[r as usize][c as usize];
+                for (dr, dc) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
+                    let (mut rr, mut cc, mut seen) = (r + dr, c + dc, 0);
+                    let mut blocked = false;
+                    while (0..h).contains(&rr) && (0..w).contains(&cc) {
+                        seen += 1;
+                        if grid[rr as usize][cc as usize] >= tree {
+                            blocked = true;
+                            break;
+                        }
+                        rr += dr;
+                        cc += dc;
+                    }
+                    visible[r as usize][c as usize] |= !blocked;
+                    scenic[r as usize][c as usize] *= seen;
+                }
+            }
+        }
+        (visible, scenic)
+    }
+
+    #[test]
+    pub fn test_day_8_matrices() {
+        let analysis = Forest::parse(INPUTS).unwrap().analyze();
+        assert_eq!(analysis.scenic[1][2], 4);
+        assert_eq!(analysis.scenic[3][2], 8);
+        assert_eq!(analysis.scenic[0][0], 0);
+        let hidden = analysis
+            .visible
+            .iter()
+            .flat_map(|row| row.iter().map(|v| if *v { '#' } else { '.' }))
+            .collect::<String>();
+        assert_eq!(hidden, "########.###.###.#.######");
+    }
+
+    #[test]
+    pub fn test_day_8_matches_brute_force() {
+        let mut seed = 12345u64;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for _ in 0..200 {
+            let (h, w) = (1 + next(12) as usize, 1 + next(12) as usize);
+            let tallest = [3, 10, 1000][next(3) as usize];
+            let grid = (0..h)
+                .map(|_| (0..w).map(|_| next(tallest) as u32).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            // The trailing comma keeps a single column of numbers from
+            // being read as digits.
+            let input = grid
+                .iter()
+                .map(|r| {
+                    format!(
+                        "{},",
+                        r.iter()
+                            .map(|t| t.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let forest = Forest::parse(&input).unwrap();
+            assert_eq!((forest.width(), forest.height()), (w, h));
+            let analysis = forest.analyze();
+            assert_eq!(
+                (analysis.visible, analysis.scenic),
+                brute_force(&grid),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_day_8_parsing() {
+        let forest = Forest::parse("10, 2, 30\n4 15 6\n").unwrap();
+        assert_eq!((forest.width(), forest.height()), (3, 2));
+        assert_eq!(forest.get(0, 2), Some(30));
+        assert_eq!(forest.get(2, 0), None);
+        assert_eq!(forest.analyze().visible_count(), 6);
+
+        assert_eq!(
+            Forest::parse("123\n4x6"),
+            Err(ForestError::BadHeight {
+                line: 2,
+                token: "x".into()
+            })
+        );
+        assert_eq!(
+            Forest::parse("123\n\n45"),
+            Err(ForestError::Ragged {
+                line: 3,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(Forest::parse("").unwrap().analyze().best_scenic(), 0);
+    }
 }
@@ -2,115 +2,435 @@
 
 use std::collections::HashSet;
 
-const DEBUG: bool = false;
-
-fn print(poses: &[(isize, isize)]) {
-    if DEBUG {
-        let min_x = poses.iter().map(|p| p.0).min().unwrap().min(0);
-        let min_y = poses.iter().map(|p| p.1).min().unwrap().min(0);
-
-        let max_x = poses.iter().map(|p| p.0).max().unwrap().max(0);
-        let max_y = poses.iter().map(|p| p.1).max().unwrap().max(0);
-
-        for y in (min_y..=max_y).rev() {
-            for x in min_x..=max_x {
-                if (x, y) == poses[0] {
-                    eprint!("H");
-                } else if (x, y) == poses[1] && poses.len() == 2 {
-                    eprint!("T");
-                } else if let Some(idx) = poses.iter().position(|p| *p == (x, y)) {
-                    eprint!("{}", idx + 1);
-                } else if (x, y) == (0, 0) {
-                    eprint!("s");
-                } else {
-                    eprint!(".");
-                }
+pub type Pos = (isize, isize);
+
+/// How a knot catches up with the one ahead of it.
+pub trait FollowRule {
+    /// Whether a knot `delta` away from its leader has to move.
+    fn too_far(&self, delta: Pos) -> bool;
+
+    /// One step of a knot towards its leader.
+    fn step(&self, delta: Pos) -> Pos;
+}
+
+/// The puzzle's rule with `slack` 1: a knot moves once its leader is more
+/// than `slack` cells away in any direction, stepping diagonally if needed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Chebyshev {
+    pub slack: usize,
+}
+
+impl FollowRule for Chebyshev {
+    fn too_far(&self, delta: Pos) -> bool {
+        delta.0.unsigned_abs().max(delta.1.unsigned_abs()) > self.slack
+    }
+
+    fn step(&self, delta: Pos) -> Pos {
+        (delta.0.signum(), delta.1.signum())
+    }
+}
+
+/// A knot moves once its leader is more than `slack` cells away counting
+/// both axes, and only ever moves along one axis at a time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Manhattan {
+    pub slack: usize,
+}
+
+impl FollowRule for Manhattan {
+    fn too_far(&self, delta: Pos) -> bool {
+        delta.0.unsigned_abs() + delta.1.unsigned_abs() > self.slack
+    }
+
+    fn step(&self, delta: Pos) -> Pos {
+        if delta.0.abs() >= delta.1.abs() {
+            (delta.0.signum(), 0)
+        } else {
+            (0, delta.1.signum())
+        }
+    }
+}
+
+/// A rectangle of the plane to draw, with `y` increasing upwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub min: Pos,
+    pub max: Pos,
+}
+
+impl Window {
+    fn around(points: impl IntoIterator<Item = Pos>) -> Window {
+        points.into_iter().fold(
+            Window {
+                min: (0, 0),
+                max: (0, 0),
+            },
+            |w, p| Window {
+                min: (w.min.0.min(p.0), w.min.1.min(p.1)),
+                max: (w.max.0.max(p.0), w.max.1.max(p.1)),
+            },
+        )
+    }
+
+    fn draw(&self, cell: impl Fn(Pos) -> char) -> String {
+        let mut out = String::new();
+        for y in (self.min.1..=self.max.1).rev() {
+            for x in self.min.0..=self.max.0 {
+                out.push(cell((x, y)));
             }
-            eprintln!();
+            out.push('\n');
         }
-        eprintln!();
+        out
     }
 }
 
-fn advance(head_pos: (isize, isize), dir: &str) -> (isize, isize) {
-    match dir {
-        "L" => (head_pos.0 - 1, head_pos.1),
-        "R" => (head_pos.0 + 1, head_pos.1),
-        "U" => (head_pos.0, head_pos.1 + 1),
-        "D" => (head_pos.0, head_pos.1 - 1),
-        _ => unreachable!(),
+/// A rope whose head starts at the origin along with every other knot.
+#[derive(Debug, Clone)]
+pub struct Rope<R = Chebyshev> {
+    knots: Vec<Pos>,
+    visited: Vec<HashSet<Pos>>,
+    rule: R,
+}
+
+impl Rope<Chebyshev> {
+    /// A rope following the puzzle's rules.
+    pub fn new(knots: usize) -> Self {
+        Rope::with_rule(knots, Chebyshev { slack: 1 })
     }
 }
 
-fn follow(head_pos: (isize, isize), mut tail_pos: (isize, isize)) -> (isize, isize) {
-    let delta_x = head_pos.0 - tail_pos.0;
-    let delta_y = head_pos.1 - tail_pos.1;
+impl<R: FollowRule> Rope<R> {
+    pub fn with_rule(knots: usize, rule: R) -> Self {
+        assert!(knots > 0, "a rope needs a head");
+        Rope {
+            knots: vec![(0, 0); knots],
+            visited: vec![HashSet::from([(0, 0)]); knots],
+            rule,
+        }
+    }
+
+    pub fn knots(&self) -> &[Pos] {
+        &self.knots
+    }
 
-    if delta_x.abs() > 1 || delta_y.abs() > 1 {
-        if delta_x != 0 {
-            tail_pos.0 += delta_x.signum();
+    pub fn tail(&self) -> Pos {
+        self.knots[self.knots.len() - 1]
+    }
+
+    /// Every cell knot `idx` has been in, counting the start.
+    pub fn visited(&self, idx: usize) -> &HashSet<Pos> {
+        &self.visited[idx]
+    }
+
+    /// Moves the head by `delta`, then lets each knot in turn catch up.
+    pub fn step(&mut self, delta: Pos) {
+        self.knots[0] = (self.knots[0].0 + delta.0, self.knots[0].1 + delta.1);
+        self.visited[0].insert(self.knots[0]);
+
+        for idx in 1..self.knots.len() {
+            let leader = self.knots[idx - 1];
+            let mut knot = self.knots[idx];
+            loop {
+                let delta = (leader.0 - knot.0, leader.1 - knot.1);
+                if !self.rule.too_far(delta) {
+                    break;
+                }
+                let step = self.rule.step(delta);
+                knot = (knot.0 + step.0, knot.1 + step.1);
+                self.visited[idx].insert(knot);
+            }
+            self.knots[idx] = knot;
+        }
+    }
+
+    pub fn apply(&mut self, motion: Motion) {
+        for _ in 0..motion.steps {
+            self.step(motion.dir.delta());
         }
-        if delta_y != 0 {
-            tail_pos.1 += delta_y.signum();
+    }
+
+    /// The smallest window holding the start and every knot.
+    pub fn window(&self) -> Window {
+        Window::around(self.knots.iter().copied())
+    }
+
+    fn label(&self, idx: usize) -> char {
+        match idx {
+            0 => 'H',
+            1 if self.knots.len() == 2 => 'T',
+            _ => char::from_digit(idx as u32 % 36, 36).unwrap(),
         }
     }
 
-    tail_pos
+    /// Draws the rope like the puzzle: `H` for the head, then `T` or the
+    /// knot's number, `s` for the start and `.` elsewhere. Knots nearer the
+    /// head cover the ones behind them.
+    pub fn render(&self, window: Window) -> String {
+        window.draw(|p| match self.knots.iter().position(|k| *k == p) {
+            Some(idx) => self.label(idx),
+            None if p == (0, 0) => 's',
+            None => '.',
+        })
+    }
+
+    /// Draws the cells knot `idx` has visited as `#`, with `s` for the start.
+    pub fn render_visited(&self, idx: usize, window: Window) -> String {
+        window.draw(|p| match p {
+            (0, 0) => 's',
+            p if self.visited[idx].contains(&p) => '#',
+            _ => '.',
+        })
+    }
 }
 
-pub fn part_1(input: &str) -> usize {
-    let mut head_pos = (0isize, 0isize);
-    let mut tail_pos = head_pos;
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Dir {
+    U,
+    D,
+    L,
+    R,
+    UL,
+    UR,
+    DL,
+    DR,
+}
 
-    let mut tail_poses = HashSet::new();
+impl Dir {
+    fn parse(s: &str) -> Option<Dir> {
+        Some(match s {
+            "U" => Dir::U,
+            "D" => Dir::D,
+            "L" => Dir::L,
+            "R" => Dir::R,
+            "UL" | "LU" => Dir::UL,
+            "UR" | "RU" => Dir::UR,
+            "DL" | "LD" => Dir::DL,
+            "DR" | "RD" => Dir::DR,
+            _ => return None,
+        })
+    }
 
-    for line in input.lines() {
-        tail_poses.insert(tail_pos);
+    pub fn delta(self) -> Pos {
+        match self {
+            Dir::U => (0, 1),
+            Dir::D => (0, -1),
+            Dir::L => (-1, 0),
+            Dir::R => (1, 0),
+            Dir::UL => (-1, 1),
+            Dir::UR => (1, 1),
+            Dir::DL => (-1, -1),
+            Dir::DR => (1, -1),
+        }
+    }
+}
 
-        let mut iter = line.split_ascii_whitespace();
-        let dir = iter.next().unwrap();
-        let dist = iter.next().unwrap().parse::<isize>().unwrap();
+/// Move the head `steps` times in direction `dir`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Motion {
+    pub dir: Dir,
+    pub steps: usize,
+}
 
-        for _ in 0..dist {
-            head_pos = advance(head_pos, dir);
-            tail_pos = follow(head_pos, tail_pos);
-            tail_poses.insert(tail_pos);
-            print(&[head_pos, tail_pos]);
+/// Line numbers count from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MotionError {
+    UnknownDirection {
+        line: usize,
+        token: String,
+    },
+    BadCount {
+        line: usize,
+        token: String,
+    },
+    /// A repeat without its parenthesised group, or a stray token.
+    Syntax {
+        line: usize,
+    },
+    /// Repeats would expand the log past [`MAX_MOTIONS`].
+    TooMany {
+        line: usize,
+    },
+    /// Repeats are nested more than [`MAX_NESTING`] deep.
+    TooDeep {
+        line: usize,
+    },
+}
+
+/// The most motions a log may expand to once repeats are written out.
+pub const MAX_MOTIONS: usize = 1 << 20;
+
+/// How deeply repeat groups may be nested.
+pub const MAX_NESTING: usize = 32;
+
+/// Splits a line into runs of letters, runs of digits and punctuation.
+fn tokenize(line: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = None;
+    let class = |c: char| {
+        if c.is_ascii_alphabetic() {
+            1
+        } else if c.is_ascii_digit() {
+            2
+        } else {
+            0
         }
+    };
+    for (idx, c) in line.char_indices() {
+        if let Some(s) = start {
+            if class(c) == class(line[s..].chars().next().unwrap()) {
+                continue;
+            }
+            tokens.push(&line[s..idx]);
+            start = None;
+        }
+        match c {
+            c if c.is_whitespace() => (),
+            c if class(c) != 0 => start = Some(idx),
+            _ => tokens.push(&line[idx..idx + c.len_utf8()]),
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&line[s..]);
     }
+    tokens
+}
 
-    tail_poses.len()
+struct MotionParser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    line: usize,
+    depth: usize,
 }
 
-pub fn part_2(input: &str) -> usize {
-    let mut poses = [(0, 0); 10];
+impl<'a> MotionParser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), MotionError> {
+        if self.peek() != Some(token) {
+            return Err(MotionError::Syntax { line: self.line });
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn count(&mut self, token: &str) -> Result<usize, MotionError> {
+        token.parse().map_err(|_| MotionError::BadCount {
+            line: self.line,
+            token: token.to_string(),
+        })
+    }
 
-    let mut tail_poses = HashSet::new();
+    /// `item (("," | ";") item)*`
+    fn sequence(&mut self, out: &mut Vec<Motion>) -> Result<(), MotionError> {
+        self.item(out)?;
+        while let Some("," | ";") = self.peek() {
+            self.pos += 1;
+            self.item(out)?;
+        }
+        Ok(())
+    }
 
-    for line in input.lines() {
-        tail_poses.insert(poses[poses.len() - 1]);
+    /// `DIR [COUNT]` or `COUNT "*" "(" sequence ")"`
+    fn item(&mut self, out: &mut Vec<Motion>) -> Result<(), MotionError> {
+        let token = self.peek().ok_or(MotionError::Syntax { line: self.line })?;
+        self.pos += 1;
 
-        let mut iter = line.split_ascii_whitespace();
-        let dir = iter.next().unwrap();
-        let dist = iter.next().unwrap().parse::<isize>().unwrap();
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            let times = self.count(token)?;
+            self.expect("*")?;
+            self.expect("(")?;
+            if self.depth == MAX_NESTING {
+                return Err(MotionError::TooDeep { line: self.line });
+            }
+            self.depth += 1;
+            let mut group = vec![];
+            self.sequence(&mut group)?;
+            self.depth -= 1;
+            self.expect(")")?;
 
-        for _ in 0..dist {
-            poses[0] = advance(poses[0], dir);
+            // Every list being built is capped, so the whole log is too.
+            let total = group
+                .len()
+                .checked_mul(times)
+                .and_then(|n| n.checked_add(out.len()));
+            if total.is_none_or(|n| n > MAX_MOTIONS) {
+                return Err(MotionError::TooMany { line: self.line });
+            }
+            for _ in 0..times {
+                out.extend_from_slice(&group);
+            }
+            return Ok(());
+        }
 
-            for idx in 1..poses.len() {
-                poses[idx] = follow(poses[idx - 1], poses[idx]);
+        let dir = Dir::parse(token).ok_or_else(|| MotionError::UnknownDirection {
+            line: self.line,
+            token: token.to_string(),
+        })?;
+        let steps = match self.peek() {
+            Some(t) if t.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.pos += 1;
+                self.count(t)?
             }
-            tail_poses.insert(poses[poses.len() - 1]);
-            print(&poses);
+            _ => 1,
+        };
+        if out.len() == MAX_MOTIONS {
+            return Err(MotionError::TooMany { line: self.line });
+        }
+        out.push(Motion { dir, steps });
+        Ok(())
+    }
+}
+
+/// Parses a motion log. Besides the puzzle's `R 4` lines this accepts
+/// diagonals like `UL 2`, several motions on a line separated by `,` or `;`,
+/// repeated groups like `3*(R, U 2)`, a missing count meaning 1, and `#`
+/// comments. Repeats are written out, so the log may expand to at most
+/// [`MAX_MOTIONS`] motions, with groups nested at most [`MAX_NESTING`] deep.
+pub fn parse_motions(input: &str) -> Result<Vec<Motion>, MotionError> {
+    let mut motions = vec![];
+    for (idx, line) in input.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let mut parser = MotionParser {
+            tokens: tokenize(line),
+            pos: 0,
+            line: idx + 1,
+            depth: 0,
+        };
+        if parser.tokens.is_empty() {
+            continue;
         }
+        parser.sequence(&mut motions)?;
+        if parser.pos != parser.tokens.len() {
+            return Err(MotionError::Syntax { line: idx + 1 });
+        }
+    }
+    Ok(motions)
+}
+
+fn simulate(input: &str, knots: usize) -> usize {
+    let mut rope = Rope::new(knots);
+    for motion in parse_motions(input).unwrap() {
+        rope.apply(motion);
     }
+    rope.visited(knots - 1).len()
+}
+
+pub fn part_1(input: &str) -> usize {
+    simulate(input, 2)
+}
 
-    tail_poses.len()
+pub fn part_2(input: &str) -> usize {
+    simulate(input, 10)
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::day_9::{part_1, part_2};
+    use crate::day_9::{
+        parse_motions, part_1, part_2, Chebyshev, Dir, Manhattan, Motion, MotionError, Rope,
+        Window, MAX_MOTIONS, MAX_NESTING,
+    };
 
     const INPUTS: &str = r#"R 4
 U 4
@@ -140,4 +460,201 @@ R 2"#;
     pub fn test_day_9_part2() {
         assert_eq!(part_2(include_str!("input/day_9.txt")), 2533);
     }
+
+    // The diagrams in the prompt, grouped by the heading above them, with
+    // the notes about covered knots dropped.
+    fn prompt_frames() -> Vec<(String, Vec<String>)> {
+        let mut sections: Vec<(String, Vec<String>)> = vec![];
+        let mut frame = String::new();
+        for line in include_str!("day_9_prompt.txt").lines() {
+            let line = line.split("  (").next().unwrap();
+            if line.starts_with("== ") {
+                sections.push((line.trim_matches(|c| c == '=' || c == ' ').into(), vec![]));
+            } else if !line.is_empty() && line.chars().all(|c| ".#sHT0123456789".contains(c)) {
+                frame.push_str(line);
+                frame.push('\n');
+                continue;
+            }
+            if !frame.is_empty() {
+                if let Some(section) = sections.last_mut() {
+                    section.1.push(std::mem::take(&mut frame));
+                }
+                frame.clear();
+            }
+        }
+        sections
+    }
+
+    #[test]
+    pub fn test_day_9_frames_match_prompt() {
+        let sections = prompt_frames();
+        let small = Window {
+            min: (0, 0),
+            max: (5, 4),
+        };
+        let large = Window {
+            min: (-11, -5),
+            max: (14, 15),
+        };
+        let larger_input = "R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20";
+
+        let groups = sections
+            .split(|(h, _)| h == "Initial State")
+            .skip(1)
+            .collect::<Vec<_>>();
+        let initial = sections
+            .iter()
+            .filter(|(h, _)| h == "Initial State")
+            .collect::<Vec<_>>();
+        assert_eq!(groups.len(), 3);
+
+        for (group, (knots, window, input, per_step)) in [
+            (2, small, INPUTS, true),
+            (10, small, INPUTS, true),
+            (10, large, larger_input, false),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut rope = Rope::new(knots);
+            assert_eq!(initial[group].1[0], rope.render(window));
+
+            for (motion, (_, frames)) in
+                parse_motions(input).unwrap().into_iter().zip(groups[group])
+            {
+                let count = motion.steps;
+                let rendered = (0..count)
+                    .map(|_| {
+                        rope.step(motion.dir.delta());
+                        rope.render(window)
+                    })
+                    .collect::<Vec<_>>();
+                if per_step {
+                    assert_eq!(frames[..count], rendered);
+                } else {
+                    assert_eq!(Some(&frames[0]), rendered.last());
+                }
+            }
+
+            // The last heading is followed by the tail's visited cells, except
+            // for the middle example where the tail never moves.
+            let last = &groups[group].last().unwrap().1;
+            if group != 1 {
+                assert_eq!(
+                    last.last().unwrap(),
+                    &rope.render_visited(knots - 1, window)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_day_9_follow_rules() {
+        let mut rope = Rope::with_rule(3, Chebyshev { slack: 2 });
+        for _ in 0..5 {
+            rope.step((1, 0));
+        }
+        assert_eq!(rope.knots(), &[(5, 0), (3, 0), (1, 0)]);
+        rope.step((0, 1));
+        rope.step((0, 1));
+        assert_eq!(rope.knots(), &[(5, 2), (3, 0), (1, 0)]);
+        rope.step((0, 1));
+        assert_eq!(rope.knots(), &[(5, 3), (4, 1), (2, 1)]);
+
+        let mut rope = Rope::with_rule(2, Manhattan { slack: 1 });
+        rope.step((1, 0));
+        rope.step((0, 1));
+        assert_eq!(rope.knots(), &[(1, 1), (1, 0)]);
+        rope.step((1, 1));
+        assert_eq!(rope.knots(), &[(2, 2), (2, 1)]);
+        assert_eq!(rope.visited(1).len(), 4);
+        assert_eq!(rope.render(rope.window()), "..H\n..T\ns..\n");
+
+        // With no slack every knot sits on its leader after each step.
+        let mut rope = Rope::with_rule(3, Chebyshev { slack: 0 });
+        rope.step((1, 0));
+        rope.step((0, -1));
+        assert_eq!(rope.knots(), &[(1, -1); 3]);
+    }
+
+    #[test]
+    pub fn test_day_9_motion_parser() {
+        let motions = parse_motions(INPUTS).unwrap();
+        assert_eq!(motions.len(), 8);
+        assert_eq!(
+            motions[0],
+            Motion {
+                dir: Dir::R,
+                steps: 4
+            }
+        );
+
+        let log = "# warm up\nUL 2, DR; LU3 # back\n\n2*(R, U 2)\n";
+        let m = |dir, steps| Motion { dir, steps };
+        assert_eq!(
+            parse_motions(log),
+            Ok(vec![
+                m(Dir::UL, 2),
+                m(Dir::DR, 1),
+                m(Dir::UL, 3),
+                m(Dir::R, 1),
+                m(Dir::U, 2),
+                m(Dir::R, 1),
+                m(Dir::U, 2),
+            ])
+        );
+
+        assert_eq!(
+            parse_motions("R 4\nX 2"),
+            Err(MotionError::UnknownDirection {
+                line: 2,
+                token: "X".into()
+            })
+        );
+        assert_eq!(
+            parse_motions("R 99999999999999999999999"),
+            Err(MotionError::BadCount {
+                line: 1,
+                token: "99999999999999999999999".into()
+            })
+        );
+        assert_eq!(parse_motions("2*(R"), Err(MotionError::Syntax { line: 1 }));
+        assert_eq!(parse_motions("R 2 )"), Err(MotionError::Syntax { line: 1 }));
+
+        assert_eq!(
+            parse_motions("L\n1000000*(1000000*(R))"),
+            Err(MotionError::TooMany { line: 2 })
+        );
+        let full = format!("{}*(R)", MAX_MOTIONS - 1);
+        assert_eq!(
+            parse_motions(&format!("{full}\nU")).unwrap().len(),
+            MAX_MOTIONS
+        );
+        assert_eq!(
+            parse_motions(&format!("{full}\nU, D")),
+            Err(MotionError::TooMany { line: 2 })
+        );
+        let nested = |depth| "1*(".repeat(depth) + "R" + &")".repeat(depth);
+        assert_eq!(parse_motions(&nested(MAX_NESTING)).unwrap().len(), 1);
+        assert_eq!(
+            parse_motions(&nested(100000)),
+            Err(MotionError::TooDeep { line: 1 })
+        );
+    }
+
+    #[test]
+    pub fn test_day_9_diagonal_motions() {
+        let mut rope = Rope::new(2);
+        for motion in parse_motions("UR 3\nDL").unwrap() {
+            rope.apply(motion);
+        }
+        assert_eq!(rope.knots(), &[(2, 2), (2, 2)]);
+        assert_eq!(rope.visited(1).len(), 3);
+
+        // A diagonal head move can pull every knot diagonally.
+        let mut rope = Rope::new(10);
+        rope.apply(parse_motions("UR 20").unwrap()[0]);
+        assert_eq!(rope.tail(), (11, 11));
+        assert_eq!(rope.visited(9).len(), 12);
+    }
 }
@@ -8,6 +8,7 @@ pub mod day_15;
 pub mod day_16;
 pub mod day_17;
 pub mod day_18;
+pub mod day_18_voxel;
 pub mod day_19;
 pub mod day_2;
 pub mod day_20;
@@ -16,12 +17,17 @@ pub mod day_22;
 pub mod day_23;
 pub mod day_24;
 pub mod day_25;
+pub mod day_25_balanced;
 pub mod day_3;
 pub mod day_4;
 pub mod day_5;
 pub mod day_6;
 pub mod day_7;
+#[cfg(test)]
+pub mod day_7_diff;
 pub mod day_7_tree;
 pub mod day_7_tree_2;
+pub mod day_7_vfs;
 pub mod day_8;
 pub mod day_9;
+pub mod parallel;
@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How to spread independent pieces of work over threads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Run everything on the calling thread, which keeps profiles readable.
+    Sequential,
+    /// Run on this many scoped worker threads.
+    Threads(usize),
+}
+
+impl Parallelism {
+    /// One worker per available core.
+    pub fn available() -> Self {
+        thread::available_parallelism()
+            .map(|n| Parallelism::Threads(n.get()))
+            .unwrap_or(Parallelism::Sequential)
+    }
+
+    /// Reads the worker count from `AOC_THREADS`, falling back to
+    /// [`Parallelism::available`]. Setting it to `0` or `1` runs sequentially.
+    pub fn from_env() -> Self {
+        match std::env::var("AOC_THREADS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            Some(0 | 1) => Parallelism::Sequential,
+            Some(n) => Parallelism::Threads(n),
+            None => Parallelism::available(),
+        }
+    }
+
+    /// Applies `f` to every item, returning the results in the same order as
+    /// `items` no matter which worker finished first.
+    pub fn map<T, R, F>(self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        let workers = match self {
+            Parallelism::Threads(n) if n > 1 && items.len() > 1 => n.min(items.len()),
+            _ => return items.iter().map(f).collect(),
+        };
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(items.len()));
+
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= items.len() {
+                        break;
+                    }
+                    let r = f(&items[idx]);
+                    results.lock().unwrap().push((idx, r));
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(idx, _)| *idx);
+        results.into_iter().map(|(_, r)| r).collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::parallel::Parallelism;
+
+    #[test]
+    pub fn test_parallel_map_keeps_order() {
+        let items = (0..100u64).collect::<Vec<_>>();
+        let expected = items.iter().map(|i| i * i).collect::<Vec<_>>();
+
+        for p in [
+            Parallelism::Sequential,
+            Parallelism::Threads(1),
+            Parallelism::Threads(3),
+            Parallelism::Threads(200),
+        ] {
+            assert_eq!(p.map(&items, |i| i * i), expected);
+        }
+        assert_eq!(Parallelism::Threads(4).map(&[] as &[u64], |i| *i), vec![]);
+    }
+}